@@ -18,7 +18,9 @@ impl fmt::Display for Error {
     }
 }
 
-use std::{num::ParseIntError, ops::Range};
+use std::{cmp::Reverse, num::ParseIntError, ops::Range};
+
+use serde::{Deserialize, Serialize};
 
 use crate::CommentSide;
 
@@ -32,166 +34,443 @@ pub struct LinePair(u32, u32);
 
 // XXX: this work, but it's not pretty
 pub struct Diff {
-    path: String, // XXX: use std::path::Path?
-    #[cfg(feature = "theFuture")]
+    path: String,          // XXX: use std::path::Path?
     original_path: String, // XXX: use proper path, also
-    // XXX: also include original_path? (not needed, ATM)
-    #[cfg(feature = "debug")]
-    pub original_range: std::ops::Range<u32>,
-    #[cfg(not(feature = "debug"))]
     original_range: std::ops::Range<u32>,
     range: std::ops::Range<u32>,
     left_lines: std::vec::Vec<String>,
     right_lines: std::vec::Vec<String>,
-    context: std::vec::Vec<std::ops::Range<u32>>, // only one context needed, because it's common
-    // XXX: unless there are movements between files
-    // XXX: we are dealing with single file diffs,
-    // here, though
-    //    XXX: are multi-file diffs possible in
-    //    github in terms of comments to diffs
-    //    XXX: should be able to handle those as well
-    //    XXX: multi-file diffs do not share context,
-    //    do they?
     associated_line_pairs: std::vec::Vec<LinePair>,
-    trailing_newline: bool,
+    // the last line of a side is not terminated by a newline, i.e., `\ No newline at end of file`
+    left_no_newline: bool,
+    right_no_newline: bool,
+    section: String, // heading of the enclosing function or class, from the hunk header
+}
+
+/// Position of a block of context relative to the commented (or changed) lines
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ContextPosition {
+    Before,
+    Within,
+    After,
+}
+
+/// Unchanged lines of a hunk with their line numbers on either side
+pub struct Context {
+    pub text: String,
+    pub range: std::ops::Range<u32>,
+    pub original_range: std::ops::Range<u32>,
+    pub position: ContextPosition,
+}
+
+/// Number of context lines kept around commented lines, same as the default of `diff -u`
+pub const CONTEXT_LINES: usize = 3;
+
+/// Commented lines and their surrounding lines, as found in a hunk
+pub struct Pattern {
+    pub before: std::vec::Vec<String>,
+    pub target: std::vec::Vec<String>,
+    pub after: std::vec::Vec<String>,
+    /// expected line number of the first target line
+    pub line: u32,
+    /// heading of the function or class enclosing the target
+    pub section: Option<String>,
+}
+
+/// Location of a `Pattern` in a text
+#[derive(Debug, PartialEq)]
+pub struct Relocation {
+    /// 1-based (exclusive) range of the target lines
+    pub range: std::ops::Range<u32>,
+    /// distance to the expected line
+    pub offset: i64,
+    /// number of context lines ignored on either end of the pattern
+    pub fuzz: usize,
+    /// number of context lines matching around the target
+    pub context: usize,
+    /// found within the function or class named by the hunk header
+    pub in_section: bool,
+    /// how the lines were compared
+    pub matching: Matching,
 }
 
-#[derive(PartialEq)]
+/// Words that differ between a removed line and the line replacing it
+#[derive(Debug, PartialEq)]
+pub struct WordDiff {
+    /// number of the removed line
+    pub left: u32,
+    /// number of the added line
+    pub right: u32,
+    /// byte ranges of the removed words in the removed line
+    pub deleted: std::vec::Vec<Range<usize>>,
+    /// byte ranges of the added words in the added line
+    pub added: std::vec::Vec<Range<usize>>,
+}
+
+/// How lines of a pattern are compared to lines of a text
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Matching {
+    #[default]
+    Exact,
+    /// ignore indentation, trailing whitespace, line endings and how much whitespace there is
+    /// between words
+    IgnoreWhitespace,
+}
+
+/// All hunks of a patch that refer to the same file
+pub struct FileDiff {
+    pub path: String,
+    /// differs from `path` for renamed files
+    pub original_path: String,
+    pub hunks: std::vec::Vec<Diff>,
+}
+
+impl FileDiff {
+    fn new(path: &str) -> FileDiff {
+        FileDiff {
+            path: path.to_owned(),
+            original_path: path.to_owned(),
+            hunks: std::vec::Vec::new(),
+        }
+    }
+}
+
+const DEV_NULL: &str = "/dev/null";
+
+// `--- a/src/main.rs\t2023-11-01 12:00:00` -> `src/main.rs`
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let path = match path.split_once('\t') {
+        Some((p, _)) => p,
+        None => path,
+    };
+    path.strip_prefix(prefix).unwrap_or(path)
+}
+
+/// Correspondence of a line on one side of a hunk to the other side
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LineMapping {
+    /// unchanged line, with its line number on the other side
+    Kept(u32),
+    /// line only present on the right side, with the left line it was inserted before
+    Added(u32),
+    /// line only present on the left side, with the right line it was removed before
+    Deleted(u32),
+}
+
+#[derive(PartialEq, Clone, Copy)]
 enum LineType {
     Context,
     Addition,
     Deletion,
 }
 
+// `@@ -left_start,left_count +right_start,right_count @@`
+struct HunkHeader {
+    left_start: u32,
+    left_count: u32,
+    right_start: u32,
+    right_count: u32,
+    section: String,
+}
+
+impl HunkHeader {
+    fn parse(line: &str) -> Result<HunkHeader, Error> {
+        let data = line.strip_prefix("@@ ").ok_or(Error::Parse)?;
+        let (data, section) = data.split_once(" @@").ok_or(Error::Parse)?;
+        // the section heading is separated from the ranges by a single space
+        let section = section.strip_prefix(' ').unwrap_or(section);
+
+        let mut ranges = data.split(' ');
+        let (left_start, left_count) = HunkHeader::parse_range(ranges.next(), "-")?;
+        let (right_start, right_count) = HunkHeader::parse_range(ranges.next(), "+")?;
+
+        Ok(HunkHeader {
+            left_start,
+            left_count,
+            right_start,
+            right_count,
+            section: section.to_owned(),
+        })
+    }
+
+    // the count can be omitted, in which case it defaults to one
+    fn parse_range(range: Option<&str>, prefix: &str) -> Result<(u32, u32), Error> {
+        let range = range
+            .and_then(|r| r.strip_prefix(prefix))
+            .ok_or(Error::Parse)?;
+
+        let mut comma_sep = range.split(',');
+        let start = comma_sep
+            .next()
+            .ok_or(Error::Parse)?
+            .parse::<u32>()
+            .map_err(Error::from_parse_int_error)?;
+        let count = match comma_sep.next() {
+            Some(c) => c.parse::<u32>().map_err(Error::from_parse_int_error)?,
+            None => 1,
+        };
+
+        // an empty range names the line *after which* lines are added/removed, use the line
+        // following it like for all other ranges
+        if count == 0 {
+            return Ok((start + 1, count));
+        }
+
+        Ok((start, count))
+    }
+}
+
+// collects the lines of a single hunk, line by line
+struct HunkBuilder {
+    path: String,
+    original_path: String,
+    left_lines: std::vec::Vec<String>,
+    right_lines: std::vec::Vec<String>,
+    left_start: u32,
+    left_stop: u32,
+    right_start: u32,
+    right_stop: u32,
+    associated_line_pairs: Vec<LinePair>,
+    left_no_newline: bool,
+    right_no_newline: bool,
+    last_line_type: Option<LineType>,
+    section: String,
+}
+
+impl HunkBuilder {
+    fn new(header: &HunkHeader, path: &str, original_path: &str) -> HunkBuilder {
+        HunkBuilder {
+            path: path.to_owned(),
+            original_path: original_path.to_owned(),
+            left_lines: std::vec::Vec::new(),
+            right_lines: std::vec::Vec::new(),
+            left_start: header.left_start,
+            left_stop: header.left_start, // stop will be calculated from how many lines there are in the patch
+            right_start: header.right_start,
+            right_stop: header.right_start,
+            associated_line_pairs: vec![LinePair(header.left_start, header.right_start)],
+            left_no_newline: false,
+            right_no_newline: false,
+            last_line_type: None,
+            section: header.section.to_owned(),
+        }
+    }
+
+    // returns `None` for a `\ No newline at end of file` marker, which refers to the previous line
+    fn push(&mut self, line: &str) -> Result<Option<LineType>, Error> {
+        let line_type = if line.starts_with(' ') || line.is_empty() {
+            // some tools strip the space off of empty context lines
+            LineType::Context
+        } else if line.starts_with('-') {
+            LineType::Deletion
+        } else if line.starts_with('+') {
+            LineType::Addition
+        } else if line.starts_with('\\') {
+            match self.last_line_type.ok_or(Error::Invalid)? {
+                LineType::Context => {
+                    self.left_no_newline = true;
+                    self.right_no_newline = true;
+                }
+                LineType::Deletion => self.left_no_newline = true,
+                LineType::Addition => self.right_no_newline = true,
+            }
+            return Ok(None);
+        } else {
+            return Err(Error::Invalid);
+        };
+        self.last_line_type = Some(line_type);
+        let content = line.get(1..).unwrap_or("");
+
+        // XXX: neither addition of first or last line is always correct
+        //      could remove the last newline by comparing to received diff...
+        match line_type {
+            LineType::Context => {
+                self.left_lines.push(content.to_owned());
+                self.left_stop += 1;
+
+                self.right_lines.push(content.to_owned());
+                self.right_stop += 1;
+            }
+            LineType::Addition => {
+                self.right_lines.push(content.to_owned());
+                self.right_stop += 1;
+            }
+            LineType::Deletion => {
+                self.left_lines.push(content.to_owned());
+                self.left_stop += 1;
+            }
+        }
+
+        self.associated_line_pairs
+            .push(LinePair(self.left_stop, self.right_stop));
+
+        Ok(Some(line_type))
+    }
+
+    fn finish(self) -> Diff {
+        Diff {
+            path: self.path,
+            original_path: self.original_path,
+            original_range: self.left_start..self.left_stop,
+            range: self.right_start..self.right_stop,
+            left_lines: self.left_lines,
+            right_lines: self.right_lines,
+            associated_line_pairs: self.associated_line_pairs,
+            left_no_newline: self.left_no_newline,
+            right_no_newline: self.right_no_newline,
+            section: self.section,
+        }
+    }
+}
+
 impl Diff {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn original_path(&self) -> &str {
+        &self.original_path
+    }
+
     // XXX: pretty useless
     pub fn original_line_range(&self) -> std::ops::Range<u32> {
         self.original_range.start..self.original_range.end
     }
-    // for this, we currently assume minimal hunks with no context overlap
-    // in particular for the context generation
-    //  XXX: fix this
+
+    pub fn line_range(&self) -> std::ops::Range<u32> {
+        self.range.start..self.range.end
+    }
+
+    // GitHub truncates the `diff_hunk` of a comment after the commented line, hence the counts in
+    // the header are not checked here
     pub fn from_only_hunk(hunk: &str, path: &str) -> Result<Diff, Error> {
         if !hunk.starts_with("@@") {
             return Err(Error::Parse);
         }
 
-        let trailing_newline = if hunk.ends_with("\n") { true } else { false };
-        let mut left_lines = std::vec::Vec::<String>::new();
-        let mut right_lines = std::vec::Vec::<String>::new();
+        let mut lines = hunk.lines();
+        let header = HunkHeader::parse(lines.next().ok_or(Error::Parse)?)?;
+
+        // XXX: GitHub does not tell about renames in the comments
+        let mut builder = HunkBuilder::new(&header, path, path);
 
-        let mut right_start: u32 = 0;
-        let mut right_stop: u32 = 0;
-        let mut left_start: u32 = 0;
-        let mut left_stop: u32 = 0;
+        for line in lines {
+            builder.push(line)?;
+        }
 
-        let mut associated_line_pairs: Vec<LinePair> = Vec::new();
+        Ok(builder.finish())
+    }
 
-        let mut context = std::vec::Vec::<std::ops::Range<u32>>::new();
-        let mut context_start: u32 = 0; // 0 is not a valid line number (how about using something uninitialized?)
+    /// Parse all hunks of a single file, e.g., the `patch` of an entry in `pulls/N/files`
+    pub fn from_hunks(hunks: &str, path: &str) -> Result<Vec<Diff>, Error> {
+        let mut lines = hunks.lines().peekable();
+        let diffs = Diff::parse_hunks(&mut lines, path, path)?;
 
-        let mut previous_line_type = LineType::Context;
+        match lines.next() {
+            Some(_) => Err(Error::Parse),
+            None => Ok(diffs),
+        }
+    }
 
-        // XXX: also extract path and original_path from diff hunk
+    /// Parse a complete unified patch (e.g., the output of `git diff`) into per-file hunk lists
+    ///
+    /// Extended git headers (`index`, `new file mode`, ...) are skipped, as is anything before the
+    /// first file header.
+    pub fn from_patch(patch: &str) -> Result<Vec<FileDiff>, Error> {
+        let mut files = std::vec::Vec::<FileDiff>::new();
+        let mut lines = patch.lines().peekable();
 
-        // XXX: move first iteration out and simplify loop
-        for line in hunk.split('\n') {
+        while let Some(&line) = lines.peek() {
             if line.starts_with("@@") {
-                // XXX: pull this out of the loop and create a proper
-                // iterator over the rest of the data
-                let mut data = line
-                    .trim_start_matches("@")
-                    .trim_start_matches(" ")
-                    .trim_end_matches(" ")
-                    .trim_end_matches("@");
-
-                let mut start_index = data.find("-").ok_or(Error::Parse)? + 1;
-                let original_stop_index = data.find(" ").ok_or(Error::Parse)?;
-
-                let mut comma_sep = data[start_index..original_stop_index].split(",");
-                left_start = comma_sep
-                    .next()
-                    .ok_or(Error::Parse)?
-                    .parse::<u32>()
-                    .map_err(Error::from_parse_int_error)?;
-                left_stop = left_start; // stop will be calculated from how many lines there are in the patch
-                                        // XXX: could add cross-checking/precalculation here for later
-                                        // verification
-
-                start_index = data.find("+").ok_or(Error::Parse)? + 1;
-                data = data[start_index..].trim_start_matches(" "); // XXX start trim not necessary
-
-                comma_sep = data.split(",");
-                right_start = comma_sep
-                    .next()
-                    .ok_or(Error::Parse)?
-                    .parse::<u32>()
-                    .map_err(Error::from_parse_int_error)?;
-                right_stop = right_start;
-                context_start = right_start;
-
-                associated_line_pairs.push(LinePair(left_start, right_start));
-            } else {
-                let line_type = if line.starts_with(" ") {
-                    LineType::Context
-                } else if line.starts_with("-") {
-                    LineType::Deletion
-                } else if line.starts_with("+") {
-                    LineType::Addition
-                } else {
-                    return Err(Error::Invalid);
-                };
+                let file = files.last_mut().ok_or(Error::Parse)?;
+                let mut hunks = Diff::parse_hunks(&mut lines, &file.path, &file.original_path)?;
+                file.hunks.append(&mut hunks);
+                continue;
+            }
 
-                // XXX: neither addition of first or last line is always correct
-                //      could remove the last newline by comparing to received diff...
-                match line_type {
-                    LineType::Context => {
-                        left_lines.push(line[1..].to_owned());
-                        left_stop += 1;
+            lines.next();
 
-                        right_lines.push(line[1..].to_owned());
-                        right_stop += 1;
-                    }
-                    LineType::Addition => {
-                        right_lines.push(line[1..].to_owned());
-                        right_stop += 1;
-                    }
-                    LineType::Deletion => {
-                        left_lines.push(line[1..].to_owned());
-                        left_stop += 1;
-                    }
+            if let Some(paths) = line.strip_prefix("diff --git ") {
+                // XXX: ambiguous for paths containing " b/", `---` and `+++` will fix it up
+                let mut file = FileDiff::new(paths);
+                if let Some(index) = paths.find(" b/") {
+                    file.path = paths[index + 3..].to_owned();
+                    file.original_path = strip_path_prefix(&paths[..index], "a/").to_owned();
                 }
-
-                // XXX: there needs to be an associated initial push, somewhere
-                associated_line_pairs.push(LinePair(left_stop, right_stop));
-
-                if previous_line_type != line_type {
-                    match line_type {
-                        LineType::Context => {
-                            // start new context
-                            context_start = right_stop - 1;
-                        }
-                        LineType::Addition | LineType::Deletion => {
-                            if previous_line_type == LineType::Context {
-                                context.push(context_start..right_stop);
-                            }
+                files.push(file);
+            } else if let Some(path) = line.strip_prefix("--- ") {
+                let path = strip_path_prefix(path, "a/");
+                // plain unified diffs have no `diff` line, so the `---` line starts the next file
+                match files.last_mut() {
+                    Some(file) if file.hunks.is_empty() => {
+                        if path != DEV_NULL {
+                            file.original_path = path.to_owned();
                         }
                     }
+                    _ => files.push(FileDiff::new(path)),
+                }
+            } else if let Some(path) = line.strip_prefix("+++ ") {
+                let file = files.last_mut().ok_or(Error::Parse)?;
+                let path = strip_path_prefix(path, "b/");
+                if path != DEV_NULL {
+                    file.path = path.to_owned();
+                }
+                // new files do not have an original
+                if file.original_path == DEV_NULL {
+                    file.original_path = file.path.to_owned();
                 }
-                previous_line_type = line_type;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                let file = files.last_mut().ok_or(Error::Parse)?;
+                file.original_path = path.to_owned();
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                let file = files.last_mut().ok_or(Error::Parse)?;
+                file.path = path.to_owned();
             }
         }
 
-        Ok(Diff {
-            path: path.to_owned(),
-            original_range: left_start..left_stop,
-            range: right_start..right_stop,
-            left_lines,
-            right_lines,
-            context,
-            associated_line_pairs,
-            trailing_newline,
-        })
+        Ok(files)
+    }
+
+    // consumes consecutive hunks, using the counts in the headers to find the end of each hunk
+    fn parse_hunks<'a, I>(
+        lines: &mut std::iter::Peekable<I>,
+        path: &str,
+        original_path: &str,
+    ) -> Result<Vec<Diff>, Error>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut diffs = std::vec::Vec::<Diff>::new();
+
+        while let Some(line) = lines.next_if(|l| l.starts_with("@@")) {
+            let header = HunkHeader::parse(line)?;
+            let mut builder = HunkBuilder::new(&header, path, original_path);
+
+            let mut left_remaining = header.left_count;
+            let mut right_remaining = header.right_count;
+
+            while left_remaining > 0 || right_remaining > 0 {
+                let line = lines.next().ok_or(Error::Invalid)?;
+
+                // markers refer to the previous line and do not count
+                let (left, right) = match builder.push(line)? {
+                    Some(LineType::Context) => (1, 1),
+                    Some(LineType::Deletion) => (1, 0),
+                    Some(LineType::Addition) => (0, 1),
+                    None => (0, 0),
+                };
+                left_remaining = left_remaining.checked_sub(left).ok_or(Error::Invalid)?;
+                right_remaining = right_remaining.checked_sub(right).ok_or(Error::Invalid)?;
+            }
+
+            if let Some(marker) = lines.next_if(|l| l.starts_with('\\')) {
+                builder.push(marker)?;
+            }
+
+            diffs.push(builder.finish());
+        }
+
+        Ok(diffs)
     }
 
     pub fn text(&self) -> String {
@@ -204,7 +483,7 @@ impl Diff {
             out.push_str("\n"); // XXX: superfluous?/could check hunk if it contains a trailing \n
         }
 
-        if !self.trailing_newline {
+        if self.right_no_newline {
             out = match out.strip_suffix("\n") {
                 Some(v) => v.to_owned(),
                 None => out,
@@ -219,7 +498,7 @@ impl Diff {
         // line-ending character
     }
 
-    pub async fn text_part(&self, comment: Range<u32>, side: CommentSide) -> Result<String, Error> {
+    pub fn text_part(&self, comment: Range<u32>, side: CommentSide) -> Result<String, Error> {
         let mut out = String::new();
 
         // XXX: this needs to be from the correct side (original might not be the one...)
@@ -229,7 +508,15 @@ impl Diff {
         // XXX: should this be a function?
         // XXX: debug start and end
         let (lines, diff_start, diff_end) = match side {
-            CommentSide::LR | CommentSide::RL => panic!("not implemented"),
+            CommentSide::LR | CommentSide::RL => {
+                // the selection ends on the side named first
+                let on_left = matches!(side, CommentSide::LR);
+                for line in self.mixed_lines(comment.clone(), side)? {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                return Ok(self.strip_trailing_newline(out, on_left, comment.end - 1));
+            }
             CommentSide::LL => (
                 &self.left_lines,
                 self.associated_line_pairs[0].0,
@@ -261,14 +548,171 @@ impl Diff {
             out.push_str("\n"); // XXX: superfluous?/could check hunk if it contains a trailing \n
         }
 
-        if !self.trailing_newline {
-            out = match out.strip_suffix("\n") {
-                Some(v) => v.to_owned(),
-                None => out,
+        let on_left = matches!(side, CommentSide::LL);
+        Ok(self.strip_trailing_newline(out, on_left, comment.end - 1))
+    }
+
+    // only the very last line of a side might be missing its newline
+    fn strip_trailing_newline(&self, out: String, on_left: bool, last_line: u32) -> String {
+        let no_newline = match on_left {
+            true => self.left_no_newline && last_line + 1 == self.original_range.end,
+            false => self.right_no_newline && last_line + 1 == self.range.end,
+        };
+        if !no_newline {
+            return out;
+        }
+
+        match out.strip_suffix('\n') {
+            Some(v) => v.to_owned(),
+            None => out,
+        }
+    }
+
+    // one entry per line of the hunk: the type of the line and its line numbers on the left and
+    // right side (for additions and deletions the number of the following line on the other side)
+    fn rows(&self) -> impl Iterator<Item = (LineType, u32, u32)> + '_ {
+        self.associated_line_pairs.windows(2).map(|pairs| {
+            let (LinePair(left, right), LinePair(next_left, next_right)) = (&pairs[0], &pairs[1]);
+            let line_type = match (next_left - left, next_right - right) {
+                (1, 1) => LineType::Context,
+                (1, 0) => LineType::Deletion,
+                _ => LineType::Addition,
+            };
+            (line_type, *left, *right)
+        })
+    }
+
+    /// Map a line number of the original (left) side to the new (right) side
+    ///
+    /// Returns `None` for lines outside of the hunk.
+    pub fn to_new_line(&self, original_line: u32) -> Option<LineMapping> {
+        self.rows()
+            .find(|(line_type, left, _)| *line_type != LineType::Addition && *left == original_line)
+            .map(|(line_type, _, right)| match line_type {
+                LineType::Deletion => LineMapping::Deleted(right),
+                LineType::Context | LineType::Addition => LineMapping::Kept(right),
+            })
+    }
+
+    /// Map a line number of the new (right) side to the original (left) side
+    ///
+    /// Returns `None` for lines outside of the hunk.
+    pub fn to_original_line(&self, line: u32) -> Option<LineMapping> {
+        self.rows()
+            .find(|(line_type, _, right)| *line_type != LineType::Deletion && *right == line)
+            .map(|(line_type, left, _)| match line_type {
+                LineType::Addition => LineMapping::Added(left),
+                LineType::Context | LineType::Deletion => LineMapping::Kept(left),
+            })
+    }
+
+    // indices of the rows covered by a comment, the selection of a comment that starts on one side
+    // and ends on the other one covers the lines of the hunk in between
+    fn selected_rows(&self, comment: Range<u32>, side: CommentSide) -> Result<Range<usize>, Error> {
+        // `side` names the side of the last line first and the side of the first line second
+        let (start_on_left, end_on_left) = match side {
+            CommentSide::LL => (true, true),
+            CommentSide::RR => (false, false),
+            CommentSide::RL => (true, false),
+            CommentSide::LR => (false, true),
+        };
+
+        let on_side = |line_type: LineType, left: u32, right: u32, line: u32, on_left: bool| {
+            if on_left {
+                line_type != LineType::Addition && left == line
+            } else {
+                line_type != LineType::Deletion && right == line
+            }
+        };
+
+        let end_line = comment.end.checked_sub(1).ok_or(Error::Invalid)?;
+        let start = self
+            .rows()
+            .position(|(t, l, r)| on_side(t, l, r, comment.start, start_on_left))
+            .ok_or(Error::Invalid)?;
+        let end = self
+            .rows()
+            .position(|(t, l, r)| on_side(t, l, r, end_line, end_on_left))
+            .ok_or(Error::Invalid)?;
+
+        if end < start {
+            return Err(Error::Invalid);
+        }
+
+        Ok(start..end + 1)
+    }
+
+    // for mixed selections, deletions are taken from the left, everything else from the right side
+    fn mixed_lines(&self, comment: Range<u32>, side: CommentSide) -> Result<Vec<&str>, Error> {
+        let selected = self.selected_rows(comment, side)?;
+
+        Ok(self
+            .rows()
+            .skip(selected.start)
+            .take(selected.len())
+            .map(|(line_type, left, right)| match line_type {
+                LineType::Deletion => self.left_line(left),
+                LineType::Context | LineType::Addition => self.right_line(right),
+            })
+            .collect())
+    }
+
+    fn left_line(&self, line: u32) -> &str {
+        &self.left_lines[(line - self.original_range.start) as usize]
+    }
+
+    fn right_line(&self, line: u32) -> &str {
+        &self.right_lines[(line - self.range.start) as usize]
+    }
+
+    /// The commented lines together with the lines surrounding them in the hunk
+    ///
+    /// Left-sided comments are made of the lines of the left side, all others of the lines of the
+    /// right side, i.e., for mixed selections the deleted lines are left out.
+    pub fn pattern(&self, comment: Range<u32>, side: CommentSide) -> Result<Pattern, Error> {
+        let on_left = matches!(side, CommentSide::LL);
+        let selected = self.selected_rows(comment, side)?;
+
+        let mut pattern = Pattern {
+            before: std::vec::Vec::new(),
+            target: std::vec::Vec::new(),
+            after: std::vec::Vec::new(),
+            line: 0,
+            section: match self.section.is_empty() {
+                true => None,
+                false => Some(self.section.to_owned()),
+            },
+        };
+
+        for (index, (line_type, left, right)) in self.rows().enumerate() {
+            let (line_number, line) = match (on_left, line_type) {
+                (true, LineType::Addition) | (false, LineType::Deletion) => continue,
+                (true, _) => (left, self.left_line(left)),
+                (false, _) => (right, self.right_line(right)),
             };
+
+            if index < selected.start {
+                pattern.before.push(line.to_owned());
+            } else if index < selected.end {
+                if pattern.target.is_empty() {
+                    pattern.line = line_number;
+                }
+                pattern.target.push(line.to_owned());
+            } else {
+                pattern.after.push(line.to_owned());
+            }
+        }
+
+        if pattern.target.is_empty() {
+            return Err(Error::Invalid);
         }
 
-        return Ok(out);
+        // like in a regular unified diff, only the closest context is relevant
+        let skip = pattern.before.len().saturating_sub(CONTEXT_LINES);
+        pattern.before.drain(..skip);
+        pattern.after.truncate(CONTEXT_LINES);
+
+        Ok(pattern)
     }
 
     pub fn original_text(&self) -> String {
@@ -279,7 +723,7 @@ impl Diff {
             out.push_str("\n"); // XXX: superfluous?
         }
 
-        if !self.trailing_newline {
+        if self.left_no_newline {
             out = match out.strip_suffix("\n") {
                 Some(v) => v.to_owned(),
                 None => out,
@@ -292,31 +736,566 @@ impl Diff {
         // out.trim_end_matches("\n").to_owned()
     }
 
-    // XXX: this guy should return an iterator of String because we might have multiple contexts
-    //      also supply properly expected line numbers to be able to judge if after or before
-    //      change
-    // XXX: implement missing _ignore_starting stuff
-    pub fn get_context(&self, _ignore_starting: Option<u32>) -> Option<Vec<String>> {
-        let mut res = Vec::<String>::new();
+    /// Blocks of unchanged lines in the hunk
+    ///
+    /// The position of a block is given relative to the `comment`ed lines (right side), context
+    /// lines inside of the comment are not returned. Without a comment, the position is relative
+    /// to the changed lines of the hunk.
+    pub fn get_context(&self, comment: Option<Range<u32>>) -> Option<Vec<Context>> {
+        let skip_anchor = comment.is_some();
+        let anchor = match comment {
+            Some(c) => c,
+            None => self.changed_range(),
+        };
 
-        if self.context.len() == 0 {
-            return None;
-        }
+        let mut res = Vec::<Context>::new();
+        let mut in_block = false;
 
-        for ctx in self.context.iter() {
-            let mut tmp = String::new();
-            for i in ctx.to_owned() {
-                tmp.push_str(&self.right_lines[(i - self.range.start) as usize]);
-                tmp.push_str("\n");
+        for (line_type, left, right) in self.rows() {
+            // commented context lines are not context to the comment
+            if line_type != LineType::Context || (skip_anchor && anchor.contains(&right)) {
+                in_block = false;
+                continue;
             }
 
-            res.push(tmp.to_owned());
+            let position = if right < anchor.start {
+                ContextPosition::Before
+            } else if right >= anchor.end {
+                ContextPosition::After
+            } else {
+                ContextPosition::Within
+            };
+
+            let line = &self.right_lines[(right - self.range.start) as usize];
+
+            match res.last_mut() {
+                Some(ctx) if in_block && ctx.position == position => {
+                    ctx.text.push_str(line);
+                    ctx.text.push('\n');
+                    ctx.range.end += 1;
+                    ctx.original_range.end += 1;
+                }
+                _ => res.push(Context {
+                    text: format!("{line}\n"),
+                    range: right..right + 1,
+                    original_range: left..left + 1,
+                    position,
+                }),
+            }
+            in_block = true;
         }
 
         // XXX: remove trailing_whitespace if required
 
+        if res.is_empty() {
+            return None;
+        }
+
         Some(res)
     }
+
+    // right-side lines from the first to the last changed line
+    fn changed_range(&self) -> Range<u32> {
+        let mut changed = self
+            .rows()
+            .filter(|(line_type, _, _)| *line_type != LineType::Context)
+            .map(|(line_type, _, right)| match line_type {
+                LineType::Addition => right..right + 1,
+                LineType::Context | LineType::Deletion => right..right,
+            });
+
+        let first = match changed.next() {
+            Some(r) => r,
+            None => return self.range.end..self.range.end,
+        };
+
+        match changed.last() {
+            Some(last) => first.start..last.end,
+            None => first,
+        }
+    }
+
+    /// Word-level differences of the changed lines
+    ///
+    /// Within each block of removed lines followed by added lines, the lines are paired up in
+    /// order, like `git diff --word-diff` does. Lines without a partner are changed as a whole and
+    /// left out.
+    pub fn word_diffs(&self) -> Vec<WordDiff> {
+        let mut res = std::vec::Vec::new();
+        let mut deleted = std::vec::Vec::<u32>::new();
+        let mut added = std::vec::Vec::<u32>::new();
+
+        // the trailing context row ends the last block
+        let rows = self
+            .rows()
+            .chain(std::iter::once((LineType::Context, 0, 0)));
+        for (line_type, left, right) in rows {
+            match line_type {
+                LineType::Deletion if added.is_empty() => {
+                    deleted.push(left);
+                    continue;
+                }
+                LineType::Addition => {
+                    added.push(right);
+                    continue;
+                }
+                _ => (),
+            }
+
+            for (&left, &right) in deleted.iter().zip(added.iter()) {
+                let (removed, inserted) = word_diff(self.left_line(left), self.right_line(right));
+                res.push(WordDiff {
+                    left,
+                    right,
+                    deleted: removed,
+                    added: inserted,
+                });
+            }
+            deleted.clear();
+            added.clear();
+
+            if line_type == LineType::Deletion {
+                deleted.push(left);
+            }
+        }
+
+        res
+    }
+
+    /// The hunk with the changed words marked like `git diff --word-diff=plain` does, i.e.,
+    /// `[-removed-]` and `{+added+}`
+    pub fn marked_text(&self) -> String {
+        let words = self.word_diffs();
+        let mut out = String::new();
+        // writing to a `String` does not fail
+        let _ = self.render(&mut out, Some(&words));
+        out
+    }
+
+    // the hunk in unified format, with the changed words marked if `words` are given
+    fn render(&self, out: &mut impl fmt::Write, words: Option<&[WordDiff]>) -> fmt::Result {
+        writeln!(out, "{}", self.header())?;
+
+        for (line_type, left, right) in self.rows() {
+            let (prefix, line, ranges, (open, close)) = match line_type {
+                LineType::Context => (' ', self.left_line(left), None, ("", "")),
+                LineType::Deletion => (
+                    '-',
+                    self.left_line(left),
+                    words
+                        .and_then(|w| w.iter().find(|w| w.left == left))
+                        .map(|w| &w.deleted),
+                    ("[-", "-]"),
+                ),
+                LineType::Addition => (
+                    '+',
+                    self.right_line(right),
+                    words
+                        .and_then(|w| w.iter().find(|w| w.right == right))
+                        .map(|w| &w.added),
+                    ("{+", "+}"),
+                ),
+            };
+
+            out.write_char(prefix)?;
+            let mut done = 0;
+            for range in ranges.into_iter().flatten() {
+                out.write_str(&line[done..range.start])?;
+                out.write_str(open)?;
+                out.write_str(&line[range.clone()])?;
+                out.write_str(close)?;
+                done = range.end;
+            }
+            writeln!(out, "{}", &line[done..])?;
+
+            let on_left = line_type != LineType::Addition;
+            let on_right = line_type != LineType::Deletion;
+            let left_missing =
+                on_left && self.left_no_newline && left + 1 == self.original_range.end;
+            let right_missing = on_right && self.right_no_newline && right + 1 == self.range.end;
+            if left_missing || right_missing {
+                writeln!(out, "\\ No newline at end of file")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn header(&self) -> String {
+        let start = |range: &Range<u32>| match range.is_empty() {
+            true => range.start - 1,
+            false => range.start,
+        };
+
+        let mut header = format!(
+            "@@ -{},{} +{},{} @@",
+            start(&self.original_range),
+            self.original_range.len(),
+            start(&self.range),
+            self.range.len()
+        );
+        if !self.section.is_empty() {
+            header.push(' ');
+            header.push_str(&self.section);
+        }
+        header
+    }
+}
+
+// split a line into runs of word characters, runs of whitespace and single other characters,
+// given as byte ranges
+fn tokens(line: &str) -> Vec<Range<usize>> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+
+    let mut res = std::vec::Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if class(c) != 2 {
+            while let Some((index, next)) = chars.next_if(|&(_, next)| class(next) == class(c)) {
+                end = index + next.len_utf8();
+            }
+        }
+        res.push(start..end);
+    }
+
+    res
+}
+
+// byte ranges of the tokens only in `old` and of the ones only in `new`, based on their longest
+// common subsequence
+fn word_diff(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let (old_tokens, new_tokens) = (tokens(old), tokens(new));
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    let same = |i: usize, j: usize| old[old_tokens[i].clone()] == new[new_tokens[j].clone()];
+
+    // length of the common subsequence of the tokens from i and j on
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = match same(i, j) {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut deleted = std::vec::Vec::new();
+    let mut added = std::vec::Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && same(i, j) {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            extend(&mut deleted, old_tokens[i].clone());
+            i += 1;
+        } else {
+            extend(&mut added, new_tokens[j].clone());
+            j += 1;
+        }
+    }
+
+    (deleted, added)
+}
+
+// add the range, merging it into the previous one if they touch
+fn extend(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// Renders the hunk in unified diff format, such that parsing it gives the same `Diff`
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.render(f, None)
+    }
+}
+
+/// Find the pattern in the text, similar to how `patch` applies a hunk
+///
+/// All places at which the pattern matches are collected, and the one closest to the expected line
+/// is chosen. Equally distant candidates are told apart by how much of the surrounding context
+/// matches. If the pattern cannot be found anywhere, up to `max_fuzz` context lines are dropped
+/// from the outer ends of the pattern and the search is repeated, first within the function or
+/// class named by the section heading of the hunk, then in the whole text. The commented lines
+/// themselves always need to match.
+///
+/// Ignoring whitespace, the lines are compared exactly first and normalized only if that does not
+/// give a match without fuzz.
+pub fn relocate(
+    text: &str,
+    pattern: &Pattern,
+    max_fuzz: usize,
+    matching: Matching,
+) -> Option<Relocation> {
+    let lines: Vec<&str> = text.lines().collect();
+    // the scope is told by indentation, which is why it is looked for in the original lines
+    let scope = pattern
+        .section
+        .as_deref()
+        .and_then(|section| section_scope(&lines, section, pattern.line));
+
+    let exact = relocate_lines(&lines, scope.clone(), pattern, max_fuzz);
+    match (matching, exact) {
+        (Matching::Exact, exact) => exact,
+        (Matching::IgnoreWhitespace, Some(exact)) if exact.fuzz == 0 => Some(exact),
+        (Matching::IgnoreWhitespace, exact) => {
+            let normalized: Vec<String> = lines.iter().map(|l| normalize_whitespace(l)).collect();
+            let normalized: Vec<&str> = normalized.iter().map(|l| l.as_str()).collect();
+            let relocation = relocate_lines(&normalized, scope, &pattern.normalized(), max_fuzz)
+                .map(|relocation| Relocation {
+                    matching: Matching::IgnoreWhitespace,
+                    ..relocation
+                });
+
+            match (exact, relocation) {
+                (Some(exact), Some(relocation)) if relocation.fuzz < exact.fuzz => Some(relocation),
+                (Some(exact), _) => Some(exact),
+                (None, relocation) => relocation,
+            }
+        }
+    }
+}
+
+fn normalize_whitespace(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Pattern {
+    fn normalized(&self) -> Pattern {
+        let normalize = |lines: &[String]| lines.iter().map(|l| normalize_whitespace(l)).collect();
+        Pattern {
+            before: normalize(&self.before),
+            target: normalize(&self.target),
+            after: normalize(&self.after),
+            line: self.line,
+            section: self.section.to_owned(),
+        }
+    }
+}
+
+fn relocate_lines(
+    lines: &[&str],
+    scope: Option<Range<usize>>,
+    pattern: &Pattern,
+    max_fuzz: usize,
+) -> Option<Relocation> {
+    let everywhere = 0..lines.len();
+
+    if let Some(relocation) = search(lines, everywhere.clone(), pattern, 0..=0) {
+        return Some(relocation);
+    }
+
+    if let Some(scope) = scope {
+        if let Some(relocation) = search(lines, scope, pattern, 1..=max_fuzz) {
+            return Some(Relocation {
+                in_section: true,
+                ..relocation
+            });
+        }
+    }
+
+    search(lines, everywhere, pattern, 1..=max_fuzz)
+}
+
+// match the pattern with increasing fuzz, only considering matches that lie completely within
+// `scope` (indices into `lines`)
+fn search(
+    lines: &[&str],
+    scope: Range<usize>,
+    pattern: &Pattern,
+    fuzzes: std::ops::RangeInclusive<usize>,
+) -> Option<Relocation> {
+    for fuzz in fuzzes {
+        // dropping more lines than there are context lines does not change anything
+        if fuzz > 0 && fuzz > pattern.before.len() && fuzz > pattern.after.len() {
+            break;
+        }
+
+        let before = &pattern.before[fuzz.min(pattern.before.len())..];
+        let after = &pattern.after[..pattern.after.len() - fuzz.min(pattern.after.len())];
+
+        let needle: Vec<&str> = before
+            .iter()
+            .chain(pattern.target.iter())
+            .chain(after.iter())
+            .map(|l| l.as_str())
+            .collect();
+
+        if needle.len() > scope.len() {
+            continue;
+        }
+
+        let best = (scope.start..=scope.end - needle.len())
+            .filter(|&start| lines[start..start + needle.len()] == needle[..])
+            .map(|start| {
+                let target_start = start + before.len();
+                let offset = target_start as i64 - (pattern.line as i64 - 1);
+                let context = matching_context(lines, target_start, pattern);
+                (target_start, offset, context)
+            })
+            // prefer later lines, same as `patch` does
+            .min_by_key(|&(_, offset, context)| (offset.abs(), Reverse(context), offset < 0));
+
+        if let Some((target_start, offset, context)) = best {
+            let start = target_start as u32 + 1;
+            return Some(Relocation {
+                range: start..start + pattern.target.len() as u32,
+                offset,
+                fuzz,
+                context,
+                in_section: false,
+                matching: Matching::Exact,
+            });
+        }
+    }
+
+    None
+}
+
+// indices of the lines of the function or class starting with the `section` heading closest to
+// `line`: the heading, the blank or further indented lines after it, and a closing bracket on the
+// level of the heading
+fn section_scope(lines: &[&str], section: &str, line: u32) -> Option<Range<usize>> {
+    // git cuts long headings short
+    let section = section.trim();
+    if section.is_empty() {
+        return None;
+    }
+
+    let start = (0..lines.len())
+        .filter(|&index| lines[index].trim_start().starts_with(section))
+        .min_by_key(|&index| (index as i64 - (line as i64 - 1)).abs())?;
+
+    let indentation = |l: &str| l.len() - l.trim_start().len();
+    let level = indentation(lines[start]);
+
+    let mut end = start + 1;
+    while end < lines.len() {
+        let l = lines[end];
+        if l.trim().is_empty() || indentation(l) > level {
+            end += 1;
+            continue;
+        }
+        if l.trim_start().starts_with(['}', ')', ']']) {
+            end += 1;
+        }
+        break;
+    }
+
+    Some(start..end)
+}
+
+// number of context lines of the pattern that match the lines around the target, starting from
+// the target going outwards
+fn matching_context(lines: &[&str], target_start: usize, pattern: &Pattern) -> usize {
+    let target_end = (target_start + pattern.target.len()).min(lines.len());
+
+    let before = pattern
+        .before
+        .iter()
+        .rev()
+        .zip(lines[..target_start].iter().rev())
+        .take_while(|(expected, line)| expected == *line)
+        .count();
+    let after = pattern
+        .after
+        .iter()
+        .zip(lines[target_end..].iter())
+        .take_while(|(expected, line)| expected == *line)
+        .count();
+
+    before + after
+}
+
+/// How lines of a file are carried over through one or more diffs
+#[derive(Debug, PartialEq, Clone)]
+pub enum Tracking {
+    /// all of the lines are unchanged
+    Kept(std::ops::Range<u32>),
+    /// some of the lines were changed, the range covers the ones still present
+    Modified(std::ops::Range<u32>),
+    /// none of the lines are left, with the line they were removed before
+    Deleted(u32),
+}
+
+impl Tracking {
+    /// Carry the tracked lines over to the new side of the hunks of another diff
+    pub fn follow(self, hunks: &[Diff]) -> Tracking {
+        match self {
+            Tracking::Kept(lines) => track_range(hunks, lines),
+            Tracking::Modified(lines) => match track_range(hunks, lines) {
+                Tracking::Kept(lines) | Tracking::Modified(lines) => Tracking::Modified(lines),
+                deleted => deleted,
+            },
+            Tracking::Deleted(line) => match track_line(hunks, line) {
+                LineMapping::Kept(l) | LineMapping::Deleted(l) | LineMapping::Added(l) => {
+                    Tracking::Deleted(l)
+                }
+            },
+        }
+    }
+}
+
+/// Map an original line through all hunks of a file to the new side
+///
+/// In contrast to `Diff::to_new_line`, lines in between the hunks are mapped as well.
+pub fn track_line(hunks: &[Diff], original_line: u32) -> LineMapping {
+    let mut delta: i64 = 0;
+
+    for hunk in hunks {
+        if let Some(mapping) = hunk.to_new_line(original_line) {
+            return mapping;
+        }
+
+        if hunk.original_range.end > original_line {
+            break;
+        }
+
+        delta += hunk.range.len() as i64 - hunk.original_range.len() as i64;
+    }
+
+    LineMapping::Kept((original_line as i64 + delta) as u32)
+}
+
+/// Map a range of original lines through all hunks of a file to the new side
+pub fn track_range(hunks: &[Diff], original_lines: Range<u32>) -> Tracking {
+    let start = original_lines.start;
+    let mapped: Vec<LineMapping> = original_lines.map(|l| track_line(hunks, l)).collect();
+
+    let kept: Vec<u32> = mapped
+        .iter()
+        .filter_map(|m| match m {
+            LineMapping::Kept(l) => Some(*l),
+            LineMapping::Added(_) | LineMapping::Deleted(_) => None,
+        })
+        .collect();
+
+    match (kept.first(), kept.last()) {
+        (Some(&first), Some(&last)) => {
+            // lines added in between count as modification as well
+            if kept.len() == mapped.len() && (last - first + 1) as usize == kept.len() {
+                Tracking::Kept(first..last + 1)
+            } else {
+                Tracking::Modified(first..last + 1)
+            }
+        }
+        _ => match mapped.first() {
+            Some(LineMapping::Deleted(l)) => Tracking::Deleted(*l),
+            _ => Tracking::Deleted(start),
+        },
+    }
 }
 
 // the typical expectation is that the context is not changed, but rather the already changed lines
@@ -332,3 +1311,339 @@ impl Diff {
 // if there's no context, then we need to find another way :)
 //
 // we can also do fuzzy searching
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/old.rs b/new.rs
+similarity index 90%
+rename from old.rs
+rename to new.rs
+index 1111111..2222222 100644
+--- a/old.rs
++++ b/new.rs
+@@ -1,2 +1,2 @@ fn main() {
+--- a comment that starts like a file header
++// a comment
+ unchanged
+diff --git a/added.rs b/added.rs
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/added.rs
+@@ -0,0 +1 @@
++fn added() {}
+diff --git a/removed.rs b/removed.rs
+deleted file mode 100644
+index 4444444..0000000
+--- a/removed.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn removed() {}
+diff --git a/logo.png b/logo.png
+index 5555555..6666666 100644
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn patch_files() {
+        let files = Diff::from_patch(PATCH).unwrap();
+        let paths: Vec<_> = files
+            .iter()
+            .map(|f| (f.original_path.as_str(), f.path.as_str(), f.hunks.len()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("old.rs", "new.rs", 1),
+                ("added.rs", "added.rs", 1),
+                ("removed.rs", "removed.rs", 1),
+                ("logo.png", "logo.png", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn patch_deletion_looking_like_header() {
+        let files = Diff::from_patch(PATCH).unwrap();
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.path(), "new.rs");
+        assert_eq!(hunk.original_path(), "old.rs");
+        assert_eq!(
+            hunk.left_lines,
+            vec!["-- a comment that starts like a file header", "unchanged"]
+        );
+        assert_eq!(hunk.right_lines, vec!["// a comment", "unchanged"]);
+    }
+
+    #[test]
+    fn patch_empty_ranges() {
+        let files = Diff::from_patch(PATCH).unwrap();
+
+        // a pure insertion at the start of the file
+        let added = &files[1].hunks[0];
+        assert!(added.original_line_range().is_empty());
+        assert_eq!(added.line_range(), 1..2);
+        assert_eq!(added.text(), "fn added() {}\n");
+
+        let removed = &files[2].hunks[0];
+        assert_eq!(removed.original_line_range(), 1..2);
+        assert!(removed.line_range().is_empty());
+    }
+
+    #[test]
+    fn plain_unified_diff() {
+        let patch = "\
+--- a.txt\t2023-11-01 12:00:00
++++ a.txt\t2023-11-02 12:00:00
+@@ -2,0 +3,2 @@
++x
++y
+--- b.txt
++++ b.txt
+@@ -1 +1 @@
+-b
++B
+";
+        let files = Diff::from_patch(patch).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[0].hunks[0].line_range(), 3..5);
+        assert_eq!(files[1].path, "b.txt");
+        assert_eq!(files[1].hunks[0].right_lines, vec!["B"]);
+    }
+
+    // parse, render and parse again, the rendered hunk is returned
+    fn round_trip(hunk: &str) -> String {
+        let diff = Diff::from_only_hunk(hunk, "f.rs").unwrap();
+        let rendered = diff.to_string();
+        let again = Diff::from_only_hunk(&rendered, "f.rs").unwrap();
+
+        assert_eq!(again.original_range, diff.original_range);
+        assert_eq!(again.range, diff.range);
+        assert_eq!(again.left_lines, diff.left_lines);
+        assert_eq!(again.right_lines, diff.right_lines);
+        assert_eq!(again.left_no_newline, diff.left_no_newline);
+        assert_eq!(again.right_no_newline, diff.right_no_newline);
+        assert_eq!(again.section, diff.section);
+        assert_eq!(again.text(), diff.text());
+        assert_eq!(again.original_text(), diff.original_text());
+        rendered
+    }
+
+    #[test]
+    fn round_trip_section() {
+        let hunk = "@@ -10,3 +10,3 @@ impl Diff {\n     fn a() {}\n-    fn b() {}\n+    fn c() {}\n     fn d() {}\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_no_newline_left() {
+        let hunk = "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_no_newline_right() {
+        let hunk = "@@ -1,2 +1,2 @@\n a\n-b\n+c\n\\ No newline at end of file\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_no_newline_context() {
+        let hunk = "@@ -1,2 +1,3 @@\n+new\n a\n b\n\\ No newline at end of file\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_new_file() {
+        let hunk = "@@ -0,0 +1,2 @@\n+a\n+b\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_deleted_file() {
+        let hunk = "@@ -1,2 +0,0 @@\n-a\n-b\n";
+        assert_eq!(round_trip(hunk), hunk);
+    }
+
+    #[test]
+    fn round_trip_omitted_counts() {
+        // a count of one may be left out, it is always written out when rendering
+        let rendered = round_trip("@@ -3 +3 @@\n-a\n+b\n");
+        assert_eq!(rendered, "@@ -3,1 +3,1 @@\n-a\n+b\n");
+    }
+
+    #[test]
+    fn marked_words() {
+        let diff = Diff::from_only_hunk("@@ -1 +1 @@\n-let x = 1;\n+let y = 1;\n", "f.rs").unwrap();
+        assert_eq!(
+            diff.marked_text(),
+            "@@ -1,1 +1,1 @@\n-let [-x-] = 1;\n+let {+y+} = 1;\n"
+        );
+    }
+
+    #[test]
+    fn line_mappings() {
+        let diff = Diff::from_only_hunk("@@ -10,3 +10,3 @@\n a\n-b\n+c\n d\n", "f.rs").unwrap();
+
+        assert_eq!(diff.to_new_line(10), Some(LineMapping::Kept(10)));
+        assert_eq!(diff.to_new_line(11), Some(LineMapping::Deleted(11)));
+        assert_eq!(diff.to_new_line(12), Some(LineMapping::Kept(12)));
+        assert_eq!(diff.to_new_line(13), None);
+
+        assert_eq!(diff.to_original_line(10), Some(LineMapping::Kept(10)));
+        assert_eq!(diff.to_original_line(11), Some(LineMapping::Added(12)));
+        assert_eq!(diff.to_original_line(12), Some(LineMapping::Kept(12)));
+        assert_eq!(diff.to_original_line(9), None);
+    }
+
+    #[test]
+    fn text_part_deleted_into_added() {
+        let diff = Diff::from_only_hunk("@@ -1,3 +1,3 @@\n-a\n-b\n+c\n+d\n x\n", "f.rs").unwrap();
+        // from the first deleted line (left 1) to the last added one (right 2)
+        assert_eq!(
+            diff.text_part(1..3, CommentSide::RL).unwrap(),
+            "a\nb\nc\nd\n"
+        );
+    }
+
+    #[test]
+    fn text_part_added_into_context() {
+        let diff = Diff::from_only_hunk("@@ -1,3 +1,3 @@\n-a\n+c\n x\n-y\n+z\n", "f.rs").unwrap();
+        // from the added line (right 1) to the context line (left 2)
+        assert_eq!(diff.text_part(1..3, CommentSide::LR).unwrap(), "c\nx\n");
+    }
+
+    #[test]
+    fn text_part_invalid_selection() {
+        let diff = Diff::from_only_hunk("@@ -1,3 +1,3 @@\n-a\n-b\n+c\n+d\n x\n", "f.rs").unwrap();
+        // from the context line (left 3) back to the added line (right 1)
+        let backwards = Range { start: 3, end: 2 };
+        assert!(diff.text_part(backwards, CommentSide::RL).is_err());
+        // beyond the end of the hunk
+        assert!(diff.text_part(1..9, CommentSide::RL).is_err());
+        assert!(diff.text_part(2..9, CommentSide::RR).is_err());
+    }
+
+    // lines 5 to 7 followed through a zero-context diff
+    fn follow(hunks: &str) -> Tracking {
+        let hunks = Diff::from_hunks(hunks, "f.rs").unwrap();
+        Tracking::Kept(5..8).follow(&hunks)
+    }
+
+    #[test]
+    fn follow_insertion_before() {
+        assert_eq!(follow("@@ -2,0 +3,2 @@\n+x\n+y\n"), Tracking::Kept(7..10));
+    }
+
+    #[test]
+    fn follow_insertion_within() {
+        assert_eq!(follow("@@ -5,0 +6 @@\n+x\n"), Tracking::Modified(5..9));
+    }
+
+    #[test]
+    fn follow_partial_deletion() {
+        assert_eq!(follow("@@ -6 +5,0 @@\n-six\n"), Tracking::Modified(5..7));
+    }
+
+    #[test]
+    fn follow_full_deletion() {
+        assert_eq!(
+            follow("@@ -5,3 +4,0 @@\n-five\n-six\n-seven\n"),
+            Tracking::Deleted(5)
+        );
+    }
+
+    #[test]
+    fn follow_modified_stays_modified() {
+        let hunks = Diff::from_hunks("@@ -2,0 +3 @@\n+x\n", "f.rs").unwrap();
+        assert_eq!(
+            Tracking::Modified(5..8).follow(&hunks),
+            Tracking::Modified(6..9)
+        );
+    }
+
+    fn pattern(before: &[&str], target: &[&str], after: &[&str], line: u32) -> Pattern {
+        let owned = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect();
+        Pattern {
+            before: owned(before),
+            target: owned(target),
+            after: owned(after),
+            line,
+            section: None,
+        }
+    }
+
+    #[test]
+    fn relocate_nearest() {
+        let text = "t\na\nb\nc\nd\nt\ng\n";
+        let found = relocate(text, &pattern(&[], &["t"], &[], 5), 0, Matching::Exact).unwrap();
+        assert_eq!(found.range, 6..7);
+        assert_eq!(found.offset, 1);
+    }
+
+    #[test]
+    fn relocate_equal_distance_by_context() {
+        // `t` is two lines away on either side, only the earlier one follows `ctx`
+        let text = "a\nctx\nt\nb\nc\nzzz\nt\nd\n";
+        let found = relocate(
+            text,
+            &pattern(&["ctx"], &["t"], &["gone"], 5),
+            CONTEXT_LINES,
+            Matching::Exact,
+        )
+        .unwrap();
+        assert_eq!(found.range, 3..4);
+        assert_eq!(found.fuzz, 1);
+        assert_eq!(found.context, 1);
+    }
+
+    #[test]
+    fn relocate_equal_distance_later() {
+        // without context to tell them apart, the later one is taken
+        let text = "a\nb\nt\nc\nd\ne\nt\nf\n";
+        let found = relocate(text, &pattern(&[], &["t"], &[], 5), 0, Matching::Exact).unwrap();
+        assert_eq!(found.range, 7..8);
+    }
+
+    const SECTIONS: &str = "\
+fn a() {
+    let x = 1;
+    foo();
+}
+
+fn b() {
+    let x = 2;
+    foo();
+}
+";
+
+    #[test]
+    fn relocate_within_section() {
+        // the copy in `a` is closer, but the hunk is about `b`
+        let mut pattern = pattern(&["    let x = 9;"], &["    foo();"], &["}"], 3);
+        pattern.section = Some("fn b() {".to_owned());
+        let found = relocate(SECTIONS, &pattern, CONTEXT_LINES, Matching::Exact).unwrap();
+        assert_eq!(found.range, 8..9);
+        assert!(found.in_section);
+    }
+
+    #[test]
+    fn relocate_section_not_found() {
+        let mut pattern = pattern(&["    let x = 9;"], &["    foo();"], &["}"], 3);
+        pattern.section = Some("fn c() {".to_owned());
+        let found = relocate(SECTIONS, &pattern, CONTEXT_LINES, Matching::Exact).unwrap();
+        assert_eq!(found.range, 3..4);
+        assert!(!found.in_section);
+    }
+
+    #[test]
+    fn section_scope_ends_with_closing_bracket() {
+        let lines: Vec<&str> = SECTIONS.lines().collect();
+        assert_eq!(section_scope(&lines, "fn b() {", 8), Some(5..9));
+        assert_eq!(section_scope(&lines, "fn a() {", 8), Some(0..4));
+    }
+}
@@ -10,7 +10,7 @@ use tower_lsp::lsp_types::ServerCapabilities;
 use bytes::Bytes;
 
 use core::fmt;
-use std::num::TryFromIntError;
+use std::ops::Range;
 use std::{collections::HashMap, fs};
 
 use tower_lsp::jsonrpc;
@@ -26,6 +26,7 @@ mod diff;
 enum Error {
     SNH(String),
     NotImplemented(String),
+    InvalidComment(String),
     MissingConfig(String),
     InconsistentConfig,
     Gathering(reqwest::Error),
@@ -34,6 +35,9 @@ enum Error {
     Git(git2::Error),
     UTF8Error(std::str::Utf8Error),
     RequestError(reqwest::StatusCode),
+    RateLimited(u64),
+    Json(serde_json::Error),
+    UnreachableCommit(String),
     DiffError,
     ParseError,
 }
@@ -49,11 +53,20 @@ impl fmt::Display for Error {
             Error::YAML(_) => "YAML processing error".to_owned(),
             Error::Gathering(_) => "gathering error".to_owned(),
             Error::NotImplemented(f) => format!("{} not implemented", f),
+            Error::InvalidComment(why) => format!("comment not possible: {}", why),
             Error::IOError(_) => "I/O error".to_owned(),
             Error::MissingConfig(miss) => format!("configuration incomplete: {} missing", miss),
             Error::InconsistentConfig => "configuration inconsistent".to_owned(),
             Error::UTF8Error(_) => "UTF8 decoding error".to_owned(),
             Error::RequestError(err) => format!("Request error: {}", err),
+            Error::RateLimited(secs) => format!("rate limit exceeded, resets in {} seconds", secs),
+            Error::Json(_) => "JSON processing error".to_owned(),
+            Error::UnreachableCommit(id) => {
+                format!(
+                    "commit {} is neither available locally nor on the remote",
+                    id
+                )
+            }
             Error::DiffError => format!("Error processing diff"),
             Error::ParseError => "error parsing".to_owned(),
         };
@@ -75,7 +88,7 @@ struct ReviewComment {
     original_commit_id: String,
     /// can be null through overwritten commit (force-push/rebase)
     line: Option<u32>,
-    original_line: u32,
+    original_line: Option<u32>, // null for file-level comments
     start_line: Option<u32>,
     original_start_line: Option<u32>,
     user: User,
@@ -88,11 +101,24 @@ struct ReviewComment {
     side: Option<String>,       // in split view the side the comment applies to
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum SubjectType {
     Line,
     File,
 }
 
+/// Side of the diff a new comment is placed on, deletions are on the left
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Sides of a comment, `side` first, `start_side` second
+///
+/// A selection that starts on deleted lines and ends on added lines is `RL`.
 enum CommentSide {
     LL,
     RR,
@@ -100,16 +126,173 @@ enum CommentSide {
     LR,
 }
 
+/// How the lines of a comment were found in the current text
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reason {
+    ExactMatch,
+    OffsetMatch,
+    FuzzyMatch,
+    WhitespaceMatch,
+    ContextAnchored,
+    GitTracked,
+    OriginalLine,
+    NotFound,
+    WholeFile,
+}
+
+impl Reason {
+    /// Machine-readable name, used as the diagnostic code
+    fn code(&self) -> &'static str {
+        match self {
+            Reason::ExactMatch => "exact-match",
+            Reason::OffsetMatch => "offset-match",
+            Reason::FuzzyMatch => "fuzzy-match",
+            Reason::WhitespaceMatch => "whitespace-match",
+            Reason::ContextAnchored => "context-anchored",
+            Reason::GitTracked => "git-tracked",
+            Reason::OriginalLine => "original-line",
+            Reason::NotFound => "not-found",
+            Reason::WholeFile => "whole-file",
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::ExactMatch => write!(f, "exact match"),
+            Reason::OffsetMatch => write!(f, "offset match"),
+            Reason::FuzzyMatch => write!(f, "fuzzy match"),
+            Reason::WhitespaceMatch => write!(f, "match ignoring whitespace"),
+            Reason::ContextAnchored => write!(f, "anchored to context"),
+            Reason::GitTracked => write!(f, "tracked with git"),
+            Reason::OriginalLine => write!(f, "original line"),
+            Reason::NotFound => write!(f, "not found"),
+            Reason::WholeFile => write!(f, "comment on the whole file"),
+        }
+    }
+}
+
+/// Where a comment was found, why, and how much to trust that position
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    range: lsp_types::Range,
+    reason: Reason,
+    confidence: f32, // between 0.0 and 1.0
+}
+
+impl Location {
+    /// Confidence of a text based relocation, every level of fuzz costs some trust
+    fn of_relocation(relocation: &diff::Relocation) -> (Reason, f32) {
+        match (relocation.matching, relocation.offset, relocation.fuzz) {
+            (diff::Matching::IgnoreWhitespace, _, fuzz) => (
+                Reason::WhitespaceMatch,
+                (0.85 - 0.15 * fuzz as f32).max(0.3),
+            ),
+            (diff::Matching::Exact, 0, 0) => (Reason::ExactMatch, 1.0),
+            (diff::Matching::Exact, _, 0) => (Reason::OffsetMatch, 0.9),
+            (diff::Matching::Exact, _, fuzz) => {
+                (Reason::FuzzyMatch, (0.8 - 0.15 * fuzz as f32).max(0.3))
+            }
+        }
+    }
+
+    /// Notices about the whole file are shown on its first line
+    fn file_level(reason: Reason, confidence: f32) -> Location {
+        Location {
+            range: lsp_types::Range::default(),
+            reason,
+            confidence,
+        }
+    }
+
+    fn severity(&self) -> lsp_types::DiagnosticSeverity {
+        if self.confidence >= 0.75 {
+            lsp_types::DiagnosticSeverity::WARNING
+        } else if self.confidence >= 0.4 {
+            lsp_types::DiagnosticSeverity::INFORMATION
+        } else {
+            lsp_types::DiagnosticSeverity::HINT
+        }
+    }
+}
+
+/// Unit in which the client counts the characters of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    fn from_kind(kind: &lsp_types::PositionEncodingKind) -> Option<PositionEncoding> {
+        match kind.as_str() {
+            "utf-8" => Some(PositionEncoding::Utf8),
+            "utf-16" => Some(PositionEncoding::Utf16),
+            "utf-32" => Some(PositionEncoding::Utf32),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> lsp_types::PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => lsp_types::PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => lsp_types::PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => lsp_types::PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// Number of code units of `s`
+    fn width(&self, s: &str) -> u32 {
+        let width = match self {
+            PositionEncoding::Utf8 => s.len(),
+            PositionEncoding::Utf16 => s.encode_utf16().count(),
+            PositionEncoding::Utf32 => s.chars().count(),
+        };
+        width.try_into().unwrap_or(u32::MAX)
+    }
+}
+
+/// Outcome of locating a comment, with the steps that led to it
+struct Relocated {
+    line_range: LineRange,
+    // only logged in debug builds
+    #[cfg_attr(not(feature = "debug"), allow(dead_code))]
+    trace: Vec<String>,
+}
+
 enum LineRange {
-    InPlace(lsp_types::Range),
-    Moved(lsp_types::Range),
-    Modified(lsp_types::Range),
-    NotImplemented(lsp_types::Range),
+    InPlace(Location),
+    Moved(Location),
+    Modified(Location),
+    NotImplemented(Location),
+    // file-level comments, always shown on the first line
+    File(Location),
+    // the commented text is nowhere to be found, carries the original snippet
+    Outdated(Location, String),
+    // the commented lines were removed, either at the place they used to be or for the whole file,
+    // carries the original snippet
+    Deleted(Location, String),
     // XXX: maybe re-integrate that
     // NoText,
     // NotFound,
 }
 
+impl LineRange {
+    fn location(&self) -> &Location {
+        match self {
+            LineRange::InPlace(v)
+            | LineRange::Moved(v)
+            | LineRange::Modified(v)
+            | LineRange::NotImplemented(v)
+            | LineRange::File(v)
+            | LineRange::Outdated(v, _)
+            | LineRange::Deleted(v, _) => v,
+        }
+    }
+}
+
 // XXX: - ensure line-in-review to line-in-editor correspondence
 //      - double-check meaning of lines in GH API
 //      - only original_line appears to be mandatory
@@ -119,7 +302,6 @@ enum LineRange {
 // XXX: GitHub uses 1-based lines and lsp_types::Range uses zero-based one
 // XXX: fix understanding, but original is referring to a file from which was moved to another file
 impl ReviewComment {
-    // XXX: implement
     fn commented_side(&self) -> Result<CommentSide, Error> {
         let mut sides = if let Some(s) = &self.side {
             match s.as_str() {
@@ -140,7 +322,8 @@ impl ReviewComment {
                     _ => return Err(Error::SNH("received inconsistent GitHub data".to_owned())),
                 }
             } else {
-                "R".to_owned()
+                // single-line comments only come with a `side`
+                sides.clone()
             }),
         );
 
@@ -153,33 +336,82 @@ impl ReviewComment {
         }
     }
 
-    fn get_subject_type(&self) -> SubjectType {
-        match &self.subject_type {
-            Some(subject) => {
-                if let Some(v) = subject.find("line") {
-                    SubjectType::Line
-                } else if let Some(v) = subject.find("file") {
-                    SubjectType::File
-                } else {
-                    SubjectType::Line // XXX: incorrect
-                }
+    // the lines of a comment refer to `commit_id`, unless they had to be taken from the original
+    fn commented_commit(&self) -> &str {
+        match self.line {
+            Some(_) => &self.commit_id,
+            None => &self.original_commit_id,
+        }
+    }
+
+    /// Follow the commented lines through the history of the file into `text`
+    ///
+    /// Returns `None` for comments that cannot be tracked this way.
+    fn track_lines(&self, repo: &Repo, text: &str) -> Result<Option<diff::Tracking>, Error> {
+        match (self.get_subject_type()?, self.commented_side()?) {
+            (SubjectType::Line, CommentSide::RR) => {
+                let (beg, end) = self.comment_range()?;
+                repo.track_lines(self.commented_commit(), &self.path, beg..end, text)
+                    .map(Some)
+            }
+            (SubjectType::Line, CommentSide::LL) => match self.removal_line()? {
+                Some(line) => repo
+                    .track_lines(self.commented_commit(), &self.path, line..line + 1, text)
+                    .map(Some),
+                None => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Line of the commented version of the file at which the commented lines were removed
+    ///
+    /// Only meaningful for comments on the left side.
+    fn removal_line(&self) -> Result<Option<u32>, Error> {
+        let (beg, _) = self.comment_range()?;
+        let diff =
+            Diff::from_only_hunk(&self.diff_hunk, &self.path).map_err(Error::from_diff_error)?;
+        match diff.to_new_line(beg) {
+            Some(diff::LineMapping::Deleted(line)) | Some(diff::LineMapping::Kept(line)) => {
+                Ok(Some(line))
             }
-            None => SubjectType::Line, // XXX: incorrect, need to check if original_line and things
-                                       // are present
+            _ => Ok(None),
+        }
+    }
+
+    /// Path of the commented file in the local repository, given the renames since its commit
+    fn current_path(&self, renames: &HashMap<String, String>) -> String {
+        match renames.get(&self.path) {
+            Some(p) => p.to_owned(),
+            None => self.path.to_owned(),
+        }
+    }
+
+    fn get_subject_type(&self) -> Result<SubjectType, Error> {
+        match self.subject_type.as_deref() {
+            Some("line") => Ok(SubjectType::Line),
+            Some("file") => Ok(SubjectType::File),
+            Some(_) => Err(Error::SNH("received inconsistent GitHub data".to_owned())),
+            // comments made before GitHub knew about file-level comments, those always have a line
+            None => match self.original_line {
+                Some(_) => Ok(SubjectType::Line),
+                None => Err(Error::SNH("received inconsistent GitHub data".to_owned())),
+            },
         }
     }
     // XXX: one should get, from the diff hunk the paths that are involved
     // XXX: from the editor, one should get which one you are looking at and then determine the
     //      line ranges of interest
     //      This would not be an issue with git where we know where things are coming from
-    fn comment_range(&self) -> (u32, u32) {
+    fn comment_range(&self) -> Result<(u32, u32), Error> {
         // XXX: use start_line, if present
 
-        let (start_line, line) = if let Some(line) = self.line {
-            (self.start_line, line)
-        } else {
+        let (start_line, line) = match (self.line, self.original_line) {
+            (Some(line), _) => (self.start_line, line),
             // XXX: we could in principle fall back to `original` diff as well, if we'd want to
-            (self.original_start_line, self.original_line)
+            (None, Some(line)) => (self.original_start_line, line),
+            // file-level comments
+            (None, None) => return Err(Error::SNH("comment without lines".to_owned())),
         };
 
         let end = line + 1; // range is exclusive, so 1-based inclusive end is fine for
@@ -189,24 +421,80 @@ impl ReviewComment {
             None => end - 1,
         };
 
-        (beg, end)
+        Ok((beg, end))
+    }
+
+    /// Comments on removed lines are shown where those lines used to be
+    fn removed_lines_range(
+        &self,
+        text: &str,
+        tracked: Option<diff::Tracking>,
+        encoding: PositionEncoding,
+        trace: &mut Vec<String>,
+    ) -> Result<LineRange, Error> {
+        let (beg, end) = self.comment_range()?;
+        let diff =
+            Diff::from_only_hunk(&self.diff_hunk, &self.path).map_err(Error::from_diff_error)?;
+        let removed = diff
+            .text_part(beg..end, CommentSide::LL)
+            .map_err(Error::from_diff_error)?;
+
+        let (position, reason, confidence) = match (tracked, self.removal_line()?) {
+            (Some(diff::Tracking::Kept(rng)), _) => (Some(rng.start), Reason::GitTracked, 1.0),
+            (Some(diff::Tracking::Modified(rng)), _) => (Some(rng.start), Reason::GitTracked, 0.7),
+            (Some(diff::Tracking::Deleted(line)), _) => (Some(line), Reason::GitTracked, 0.7),
+            (None, Some(line)) => (
+                anchor_to_context(&diff, line..line, text).map(|rng| rng.start),
+                Reason::ContextAnchored,
+                0.4,
+            ),
+            (None, None) => (None, Reason::NotFound, 0.1),
+        };
+
+        trace.push(format!("removed lines used to be at: {:?}", position));
+
+        match position {
+            Some(line) => {
+                let location = Location {
+                    range: char_range(text, line..line + 1, encoding),
+                    reason,
+                    confidence,
+                };
+                Ok(LineRange::Deleted(location, removed))
+            }
+            None => Ok(LineRange::Outdated(
+                Location::file_level(Reason::NotFound, 0.1),
+                removed,
+            )),
+        }
+    }
+
+    /// Find the commented lines in `text`, the current content of the commented file
+    ///
+    /// Lines `tracked` through the git history take precedence over searching the text. Next to
+    /// the result, the steps that led to it are returned.
+    fn line_range(
+        &self,
+        text: &str,
+        tracked: Option<diff::Tracking>,
+        encoding: PositionEncoding,
+        matching: diff::Matching,
+    ) -> Result<Relocated, Error> {
+        let mut trace = std::vec::Vec::new();
+        let line_range = self.locate_lines(text, tracked, encoding, matching, &mut trace)?;
+        Ok(Relocated { line_range, trace })
     }
 
     // XXX: the range is currently 1-based, because line numbers are 1-based -> should this be so?
     // XXX: this is still very much GitHub specific
-    #[cfg(feature = "debug")]
-    // XXX: how about using an error type, instead of Result things
-    async fn line_range(&self, text: &str, client: &Client) -> Result<LineRange, Error> {
-        // XXX: new algorithm:
-        //      - check if line corresponds to the one in the diff
-        //          YES: we are done
-        //      - next check if we can find the proper context
-        //          - reduce context until proper context found
-        //              - calculate approximate new line location from diff notes
-        client
-            .log_message(lsp_types::MessageType::ERROR, "FUX| start this sucker")
-            .await;
-
+    fn locate_lines(
+        &self,
+        text: &str,
+        tracked: Option<diff::Tracking>,
+        encoding: PositionEncoding,
+        matching: diff::Matching,
+        trace: &mut Vec<String>,
+    ) -> Result<LineRange, Error> {
         // XXX: (in non-git mode) we are always looking for the two sides of the diff
         //      then, we will compare either text chunk to what's in the currently open file
         //
@@ -214,205 +502,206 @@ impl ReviewComment {
         //      of the right-hand-side, primarily, but look at the left-hand side as well, if we
         //      cannot find anything
 
-        let (beg, end) = self.comment_range();
+        if let SubjectType::File = self.get_subject_type()? {
+            return Ok(LineRange::File(Location::file_level(
+                Reason::WholeFile,
+                1.0,
+            )));
+        }
 
-        client
-            .log_message(
-                lsp_types::MessageType::ERROR,
-                format!("FUX| beg is: {}", beg),
-            )
-            .await;
+        let (beg, end) = self.comment_range()?;
+        // git knows the lines are gone, unless the text turns up somewhere else
+        let deleted = match tracked {
+            Some(diff::Tracking::Deleted(line)) => Some(line),
+            _ => None,
+        };
 
-        // XXX: original_line should always be set - what about the case where SubjectType is File
-        // XXX: continue here with getting the text_part on both sides
-        let (beg_diff, end_diff, found_diff) = match self.get_subject_type() {
-            SubjectType::File => (beg, end, false), // XXX: found_diff correct?
-            SubjectType::Line => {
-                let line_diff = end - beg;
+        trace.push(format!("beg is: {}", beg));
+
+        if let CommentSide::LL = self.commented_side()? {
+            return self.removed_lines_range(text, tracked, encoding, trace);
+        }
 
+        let (beg_diff, end_diff, found_diff, reason, confidence) = match tracked {
+            Some(diff::Tracking::Kept(rng)) => (rng.start, rng.end, true, Reason::GitTracked, 1.0),
+            Some(diff::Tracking::Modified(rng)) => {
+                (rng.start, rng.end, false, Reason::GitTracked, 0.7)
+            }
+            _ => {
                 let diff = Diff::from_only_hunk(&self.diff_hunk, &self.path)
                     .map_err(Error::from_diff_error)?;
 
-                client
-                    .log_message(
-                        lsp_types::MessageType::ERROR,
-                        format!(
-                            "FUX| original range: {}..{}\nnew lines: {}..{}",
-                            diff.original_range.start, diff.original_range.end, beg, end
-                        ),
-                    )
-                    .await;
-                // can go looking for text() and for original_text(), but it's more likely to be some
-                // variation of text()
-                // XXX: need to supply side/start_side, and corresponding line ranges
-                // XXX: somewhere here is the error, not sure what's off, though
+                let original_range = diff.original_line_range();
+                trace.push(format!(
+                    "original range: {}..{}, new lines: {}..{}",
+                    original_range.start, original_range.end, beg, end
+                ));
 
                 let commented_on_text = diff
-                    .text_part(beg..end, CommentSide::RR)
-                    // XXX: need to make a decision on whether we want to:
-                    // - return a list of text parts (one for each side)
-                    // - are there such things as purely left or right comments?
-                    // -> we can always pretend like there are
-                    // .text_part(beg..end, self.commented_side()?)
-                    .await
-                    .map_err(Error::from_diff_error)?; // XXX: again, need to find correctly sided
-                                                       // text
-                                                       // XXX: add method to get enum to correctly
-                                                       // access the commented on side
-
-                client
-                    .log_message(
-                        lsp_types::MessageType::ERROR,
-                        format!("FUX| commented on text: {}", commented_on_text),
-                    )
-                    .await;
-                if commented_on_text.len() == 0 {
-                    client
-                        .log_message(lsp_types::MessageType::ERROR, "zero-length text")
-                        .await;
-                    (beg, beg + 1, false)
+                    .text_part(beg..end, self.commented_side()?)
+                    .map_err(Error::from_diff_error)?;
+
+                trace.push(format!("commented on text: {}", commented_on_text));
+                if commented_on_text.is_empty() {
+                    trace.push("zero-length text".to_owned());
+                    (beg, beg + 1, false, Reason::OriginalLine, 0.1)
                 } else {
-                    match text.find(&commented_on_text) {
-                        Some(index) => {
-                            client
-                                .log_message(lsp_types::MessageType::ERROR, "found text")
-                                .await;
-                            // XXX: how to condense the following into one line?
-                            let n_preceding: u32 = text[..index]
-                                .matches("\n")
-                                .count()
-                                .try_into()
-                                .map_err(Error::try_from_int_error)?;
-                            (n_preceding + 1, n_preceding + 1 + line_diff, true)
+                    let pattern = diff
+                        .pattern(beg..end, self.commented_side()?)
+                        .map_err(Error::from_diff_error)?;
+
+                    // allow to drop all of the context, the fuzz is reported anyway
+                    match diff::relocate(text, &pattern, diff::CONTEXT_LINES, matching) {
+                        Some(relocation) => {
+                            trace.push(format!(
+                                "found text with offset {} and fuzz {} ({:?}){}",
+                                relocation.offset,
+                                relocation.fuzz,
+                                relocation.matching,
+                                match relocation.in_section {
+                                    true => " within the section of the hunk",
+                                    false => "",
+                                }
+                            ));
+                            let (reason, confidence) = Location::of_relocation(&relocation);
+                            (
+                                relocation.range.start,
+                                relocation.range.end,
+                                true,
+                                reason,
+                                confidence,
+                            )
                         }
                         None => {
-                            client
-                                .log_message(
-                                    lsp_types::MessageType::ERROR,
-                                    format!(
-                                        "FUX| text: {} nowhere to be found in {}",
-                                        commented_on_text, text
-                                    ),
-                                )
-                                .await;
-                            (beg, beg + 1, false)
+                            trace.push("text is nowhere to be found".to_owned());
+                            // XXX: mixed sides would need their lines mapped first
+                            let anchored = match self.commented_side()? {
+                                CommentSide::RR => anchor_to_context(&diff, beg..end, text),
+                                _ => None,
+                            };
+                            match (deleted, anchored) {
+                                (Some(line), _) => {
+                                    trace.push("Deleted".to_owned());
+                                    // shown where the lines used to be, as for removed lines
+                                    let location = Location {
+                                        range: char_range(text, line..line + 1, encoding),
+                                        reason: Reason::GitTracked,
+                                        confidence: 0.9,
+                                    };
+                                    return Ok(LineRange::Deleted(location, commented_on_text));
+                                }
+                                (None, Some(rng)) => {
+                                    trace.push("anchored to context".to_owned());
+                                    (rng.start, rng.end, false, Reason::ContextAnchored, 0.4)
+                                }
+                                (None, None) => {
+                                    trace.push("Outdated".to_owned());
+                                    return Ok(LineRange::Outdated(
+                                        Location::file_level(Reason::NotFound, 0.1),
+                                        commented_on_text,
+                                    ));
+                                }
+                            }
                         }
                     }
                 }
             }
         };
 
-        let final_range = lsp_types::Range::new(
-            // lsp_types::Position are 'zero-based line and character offset[s]'
-            // https://docs.rs/lsp-types/latest/lsp_types/struct.Position.html
-            lsp_types::Position::new(beg_diff - 1, 0),
-            lsp_types::Position::new(end_diff - 1, 0),
-        );
+        let location = Location {
+            range: char_range(text, beg_diff..end_diff, encoding),
+            reason,
+            confidence,
+        };
 
-        client
-            .log_message(
-                lsp_types::MessageType::ERROR,
-                format!("FUX| final beg: {beg_diff} end: {end_diff}"),
-            )
-            .await;
+        trace.push(format!("final beg: {beg_diff} end: {end_diff}"));
 
         if beg == beg_diff && end == end_diff && found_diff {
-            client
-                .log_message(lsp_types::MessageType::ERROR, "InPlace")
-                .await;
-            Ok(LineRange::InPlace(final_range))
+            trace.push("InPlace".to_owned());
+            Ok(LineRange::InPlace(location))
         } else if (beg_diff as i32 - beg as i32) == (end_diff as i32 - end as i32) && found_diff {
             // XXX: should also be larger than zero,
             // however that's sorta guaranteed by
             // beg/end being larger
-            client
-                .log_message(lsp_types::MessageType::ERROR, "Moved")
-                .await;
-            Ok(LineRange::Moved(final_range))
+            trace.push("Moved".to_owned());
+            Ok(LineRange::Moved(location))
         } else {
-            client
-                .log_message(lsp_types::MessageType::ERROR, "Modified")
-                .await;
-            Ok(LineRange::Modified(final_range))
+            trace.push("Modified".to_owned());
+            Ok(LineRange::Modified(location))
         }
-        /*
-        // XXX: this needs to become a robust method returning a range for the various permutations
-        // of line type types
-        let diff_relative_line_no = self.original_line - diff.original_line_range().start;
-
-        let commented_on_lines: Vec<_> = commented_on_text.split("\n").collect();
-        let text_lines: Vec<_> = text.split("\n").collect();
+    }
+}
 
-        match text_lines[end as usize]
-            .find(commented_on_lines[(diff_relative_line_no - 1) as usize])
-        {
-            Some(_) => lsp_types::Range::new(
-                lsp_types::Position::new(beg, 0),
-                lsp_types::Position::new(end, 0),
-            ),
-            None => lsp_types::Range::new(
-                lsp_types::Position::new(beg, 0),
-                lsp_types::Position::new(end, 0),
-            ), // XXX: have this path continue with regular code execution
-        }
-            */
-
-        // XXX: this is not how I thought this would go
-    }
-    #[cfg(not(feature = "debug"))]
-    fn line_range(&self, text: &str) -> Result<lsp_types::Range, Error> {
-        // XXX: new algorithm:
-        //      - check if line corresponds to the one in the diff
-        //          YES: we are done
-        //      - next check if we can find the proper context
-        //          - reduce context until proper context found
-        //              - calculate approximate new line location from diff notes
-
-        let end = self.original_line; // range is exclusive, so 1-based inclusive end is fine for
-                                      // zero-based exclusive end
-        let beg = match self.original_start_line {
-            Some(l) => l - 1, // start needs to be corrected, though
-            None => end - 1,
-        };
+// range of the 1-based `lines` of `text`, from the first to the last non-blank character, lines
+// missing from the text are covered from their start, a line 0 is taken as the first line
+fn char_range(text: &str, lines: Range<u32>, encoding: PositionEncoding) -> lsp_types::Range {
+    // lsp_types::Position are 'zero-based line and character offset[s]'
+    // https://docs.rs/lsp-types/latest/lsp_types/struct.Position.html
+    let line_at = |line: u32| text.lines().nth(line.saturating_sub(1) as usize);
 
-        let (beg, end) = match self.get_subject_type() {
-            SubjectType::File => (beg, end),
-            SubjectType::Line => {
-                let line_diff = end - beg;
+    let start_char = match line_at(lines.start) {
+        Some(line) => encoding.width(&line[..line.len() - line.trim_start().len()]),
+        None => 0,
+    };
+    let start = lsp_types::Position::new(lines.start.saturating_sub(1), start_char);
+    if lines.is_empty() {
+        return lsp_types::Range::new(start, start);
+    }
 
-                let diff = Diff::from_only_hunk(&self.diff_hunk, &self.path)
-                    .map_err(Error::from_diff_error)?;
+    let last = lines.end - 1;
+    let end_char = match line_at(last) {
+        Some(line) => encoding.width(line.trim_end()),
+        None => 0,
+    };
+    lsp_types::Range::new(
+        start,
+        lsp_types::Position::new(last.saturating_sub(1), end_char),
+    )
+}
 
-                // can go looking for text() and for original_text(), but it's more likely to be some
-                // variation of test()
-                let commented_on_text = diff.text(); // XXX: again, need to find correctly sided
-                                                     // text
-                                                     // XXX: add method to get enum to correctly
-                                                     // access the commented on side
+// place the `comment`ed lines relative to the context right before or after them, assuming that
+// the context is more likely to be unchanged than the commented lines themselves
+fn anchor_to_context(diff: &Diff, comment: Range<u32>, text: &str) -> Option<Range<u32>> {
+    let context = diff.get_context(Some(comment.clone()))?;
+
+    let leading = context
+        .iter()
+        .rev()
+        .find(|ctx| ctx.position == diff::ContextPosition::Before);
+    if let Some(ctx) = leading {
+        if let Some(found) = locate_context(ctx, text) {
+            let start = found.start + (comment.start - ctx.range.start);
+            return Some(start..start + comment.len() as u32);
+        }
+    }
 
-                let beg: u32 = if commented_on_text.len() == 0 {
-                    beg
-                } else {
-                    match text.find(&commented_on_text) {
-                        Some(index) => text[..index]
-                            .matches("\n")
-                            .count()
-                            .try_into()
-                            .map_err(Error::try_from_int_error)?,
-                        None => beg,
-                    }
-                };
+    let trailing = context
+        .iter()
+        .find(|ctx| ctx.position == diff::ContextPosition::After);
+    if let Some(ctx) = trailing {
+        if let Some(found) = locate_context(ctx, text) {
+            // lines are 1-based, the commented ones cannot start before the text does
+            let start = (found.start + comment.start)
+                .checked_sub(ctx.range.start)
+                .filter(|&s| s >= 1);
+            return start.map(|s| s..s + comment.len() as u32);
+        }
+    }
 
-                let end = beg + line_diff;
-                (beg, end)
-            }
-        };
+    None
+}
 
-        Ok(lsp_types::Range::new(
-            lsp_types::Position::new(beg, 0),
-            lsp_types::Position::new(end, 0),
-        ))
-    }
+// lines of `text` matching the block of context as a whole, the ones nearest to where the block
+// used to be are preferred
+fn locate_context(ctx: &diff::Context, text: &str) -> Option<Range<u32>> {
+    let pattern = diff::Pattern {
+        before: std::vec::Vec::new(),
+        target: ctx.text.lines().map(|l| l.to_owned()).collect(),
+        after: std::vec::Vec::new(),
+        line: ctx.range.start,
+        section: None,
+    };
+    diff::relocate(text, &pattern, 0, diff::Matching::Exact).map(|relocation| relocation.range)
 }
 
 // rustlings does it like this:
@@ -426,6 +715,9 @@ impl Error {
     fn from_yaml_error(err: serde_yaml::Error) -> Error {
         Error::YAML(err)
     }
+    fn from_json_error(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
     fn from_git_error(err: git2::Error) -> Error {
         Error::Git(err)
     }
@@ -435,9 +727,6 @@ impl Error {
     fn from_diff_error(err: diff::Error) -> Error {
         Error::DiffError
     }
-    fn try_from_int_error(err: TryFromIntError) -> Error {
-        Error::ParseError
-    }
 }
 
 // XXX: PartialEq needed for comparison in `from_args`
@@ -466,6 +755,257 @@ impl Repo {
             },
         })
     }
+
+    /// Make sure `commit_id` is available locally, fetching it from the remote if necessary
+    ///
+    /// After a force-push, commits of the pull request are not part of any local branch anymore.
+    /// They are still available from `refs/pull/N/head` as long as they are part of the pull
+    /// request, and usually by their id afterwards.
+    fn recover_commit(&self, commit_id: &str, review: &Review) -> Result<(), Error> {
+        if self.has_commit(commit_id) {
+            return Ok(());
+        }
+        let VCS::Git(repo) = &self.vcs;
+
+        let mut remote = Repo::find_remote(repo, review)?;
+        let name = remote.name().unwrap_or("origin").to_owned();
+        let pull_head = format!(
+            "+refs/pull/{id}/head:refs/remotes/{name}/pull/{id}",
+            id = review.id,
+            name = name
+        );
+
+        for refspec in [pull_head.as_str(), commit_id] {
+            let mut callbacks = git2::RemoteCallbacks::new();
+            // libgit2 asks again after rejected credentials, which would be the same ones
+            let mut attempts = 0;
+            callbacks.credentials(move |_, username, allowed| {
+                attempts += 1;
+                if attempts > 1 {
+                    return Err(git2::Error::from_str("authentication failed"));
+                }
+                if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                    let token = Review::get_authentication(&review.auth)
+                        .map_err(|e| git2::Error::from_str(&e.to_string()))?;
+                    git2::Cred::userpass_plaintext("x-access-token", &token)
+                } else {
+                    git2::Cred::ssh_key_from_agent(username.unwrap_or("git"))
+                }
+            });
+            let mut options = git2::FetchOptions::new();
+            options.remote_callbacks(callbacks);
+
+            // a failing fetch of one refspec is not fatal, the commit might come with the other one
+            if remote.fetch(&[refspec], Some(&mut options), None).is_ok()
+                && self.has_commit(commit_id)
+            {
+                return Ok(());
+            }
+        }
+
+        Err(Error::UnreachableCommit(commit_id.to_owned()))
+    }
+
+    // whether `commit_id` resolves to a commit in the local repository
+    fn has_commit(&self, commit_id: &str) -> bool {
+        let VCS::Git(repo) = &self.vcs;
+        repo.revparse_single(commit_id)
+            .and_then(|o| o.peel_to_commit())
+            .is_ok()
+    }
+
+    // the remote pointing to the reviewed repository, `origin` if there is no better match
+    fn find_remote<'r>(
+        repo: &'r git2::Repository,
+        review: &Review,
+    ) -> Result<git2::Remote<'r>, Error> {
+        let slug = format!("{}/{}", review.owner, review.repo);
+        let remotes = repo.remotes().map_err(Error::from_git_error)?;
+
+        for name in remotes.iter().flatten() {
+            let remote = repo.find_remote(name).map_err(Error::from_git_error)?;
+            let matches = match remote.url() {
+                Some(url) => url.contains(&review.url) && url.contains(&slug),
+                None => false,
+            };
+            if matches {
+                return Ok(remote);
+            }
+        }
+
+        repo.find_remote("origin").map_err(Error::from_git_error)
+    }
+
+    /// Files renamed between `commit_id` and the index (i.e., including staged renames), by their
+    /// old path
+    fn renames(&self, commit_id: &str) -> Result<HashMap<String, String>, Error> {
+        let VCS::Git(repo) = &self.vcs;
+
+        let tree = repo
+            .revparse_single(commit_id)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(Error::from_git_error)?;
+        let mut changes = repo
+            .diff_tree_to_index(Some(&tree), None, None)
+            .map_err(Error::from_git_error)?;
+
+        Repo::rename_map(&mut changes)
+    }
+
+    // new path of a file, if it was renamed in `changes`
+    fn renamed(changes: &mut git2::Diff, path: &str) -> Result<Option<String>, Error> {
+        Ok(Repo::rename_map(changes)?.remove(path))
+    }
+
+    // new paths of renamed files by their old paths
+    fn rename_map(changes: &mut git2::Diff) -> Result<HashMap<String, String>, Error> {
+        let mut opts = git2::DiffFindOptions::new();
+        opts.renames(true);
+        changes
+            .find_similar(Some(&mut opts))
+            .map_err(Error::from_git_error)?;
+
+        let renames = changes
+            .deltas()
+            .filter(|delta| delta.status() == git2::Delta::Renamed)
+            .filter_map(|delta| {
+                let old = delta.old_file().path()?.to_str()?;
+                let new = delta.new_file().path()?.to_str()?;
+                Some((old.to_owned(), new.to_owned()))
+            })
+            .collect();
+
+        Ok(renames)
+    }
+
+    /// Follow lines of a file from the state in `commit_id` through all commits up to HEAD and
+    /// from there into the current (possibly unsaved) `buffer`
+    fn track_lines(
+        &self,
+        commit_id: &str,
+        path: &str,
+        lines: Range<u32>,
+        buffer: &str,
+    ) -> Result<diff::Tracking, Error> {
+        let VCS::Git(repo) = &self.vcs;
+
+        let commit = repo
+            .revparse_single(commit_id)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(Error::from_git_error)?;
+        let head = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .map_err(Error::from_git_error)?;
+
+        let mut versions = Vec::<git2::Oid>::new();
+        if repo
+            .graph_descendant_of(head.id(), commit.id())
+            .map_err(Error::from_git_error)?
+        {
+            let mut walk = repo.revwalk().map_err(Error::from_git_error)?;
+            walk.push(head.id()).map_err(Error::from_git_error)?;
+            walk.hide(commit.id()).map_err(Error::from_git_error)?;
+            walk.simplify_first_parent()
+                .map_err(Error::from_git_error)?;
+            walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+                .map_err(Error::from_git_error)?;
+            for oid in walk {
+                versions.push(oid.map_err(Error::from_git_error)?);
+            }
+        } else if head.id() != commit.id() {
+            // not part of the checked out history, compare directly
+            versions.push(head.id());
+        }
+
+        let mut tracking = diff::Tracking::Kept(lines);
+        let mut path = path.to_owned();
+        let mut previous = Repo::file_at(repo, &commit, &path)?;
+        let mut previous_commit = commit;
+
+        for oid in versions {
+            let version = repo.find_commit(oid).map_err(Error::from_git_error)?;
+
+            let old_tree = previous_commit.tree().map_err(Error::from_git_error)?;
+            let new_tree = version.tree().map_err(Error::from_git_error)?;
+            if new_tree.get_path(std::path::Path::new(&path)).is_err() {
+                let mut changes = repo
+                    .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)
+                    .map_err(Error::from_git_error)?;
+                if let Some(renamed) = Repo::renamed(&mut changes, &path)? {
+                    path = renamed;
+                }
+            }
+
+            let current = Repo::file_at(repo, &version, &path)?;
+            if current != previous {
+                tracking = tracking.follow(&Repo::diff_texts(&previous, &current, &path)?);
+                previous = current;
+            }
+            previous_commit = version;
+        }
+
+        if previous != buffer {
+            tracking = tracking.follow(&Repo::diff_texts(&previous, buffer, &path)?);
+        }
+
+        Ok(tracking)
+    }
+
+    fn file_at(
+        repo: &git2::Repository,
+        commit: &git2::Commit,
+        path: &str,
+    ) -> Result<String, Error> {
+        let blob = commit
+            .tree()
+            .and_then(|t| t.get_path(std::path::Path::new(path)))
+            .and_then(|e| e.to_object(repo))
+            .and_then(|o| o.peel_to_blob())
+            .map_err(Error::from_git_error)?;
+
+        std::str::from_utf8(blob.content())
+            .map(|c| c.to_owned())
+            .map_err(Error::from_utf8_error)
+    }
+
+    // minimal hunks (without context) that turn `old` into `new`
+    fn diff_texts(old: &str, new: &str, path: &str) -> Result<Vec<Diff>, Error> {
+        // a missing newline at the end of the file does not change the last line for our purposes
+        let terminated = |text: &str| {
+            if text.is_empty() || text.ends_with('\n') {
+                text.to_owned()
+            } else {
+                format!("{}\n", text)
+            }
+        };
+        let (old, new) = (terminated(old), terminated(new));
+
+        let mut opts = git2::DiffOptions::new();
+        opts.context_lines(0);
+
+        let path = std::path::Path::new(path);
+        let mut patch = git2::Patch::from_buffers(
+            old.as_bytes(),
+            Some(path),
+            new.as_bytes(),
+            Some(path),
+            Some(&mut opts),
+        )
+        .map_err(Error::from_git_error)?;
+        let buf = patch.to_buf().map_err(Error::from_git_error)?;
+        let text = buf
+            .as_str()
+            .ok_or(Error::SNH("patch is not UTF-8".to_owned()))?;
+
+        // there is no file in the patch if nothing changed
+        let files = Diff::from_patch(text).map_err(Error::from_diff_error)?;
+        Ok(files
+            .into_iter()
+            .next()
+            .map(|f| f.hunks)
+            .unwrap_or_default())
+    }
 }
 
 /* this seems unnecessary
@@ -489,6 +1029,20 @@ struct Review {
     id: u32,
     comments: String,
     local_repo: String,
+    // how commented lines are compared to the current ones, may be set in the config file
+    #[serde(default)]
+    matching: diff::Matching,
+    // number of comments requested at once, GitHub allows up to 100
+    #[serde(default = "Review::default_per_page")]
+    per_page: u32,
+    // comments collected for the next review, see `submit_review`
+    #[serde(default = "Review::default_pending")]
+    pending: String,
+    #[serde(skip)]
+    http: std::sync::Mutex<HttpCache>,
+    // shared by all requests, such that connections are reused
+    #[serde(skip, default = "Review::default_client")]
+    client: reqwest::Client,
 }
 
 // cannot simply have original comments and references to it in one struct (self-referential)
@@ -539,7 +1093,10 @@ impl<'a> Conversation<'a> {
         for comment in &self.starter {
             println!("|{}|", "+".repeat(NCOL));
             println!("{}", comment.path);
-            println!("{}", comment.diff_hunk);
+            match Diff::from_only_hunk(&comment.diff_hunk, &comment.path) {
+                Ok(diff) => print!("{}", diff.marked_text()),
+                Err(_) => println!("{}", comment.diff_hunk),
+            }
             println!(
                 "[{id}]{name}: {body}",
                 id = comment.id,
@@ -584,10 +1141,74 @@ impl<'a> Conversation<'a> {
     }
 }
 
+/// A file changed by the pull request, as listed by `pulls/N/files`
+#[derive(Debug, Deserialize)]
+struct PullFile {
+    filename: String,
+    patch: Option<String>,
+}
+
+/// A page of a (paginated) response
+#[derive(Debug, Clone)]
+struct Page {
+    body: Bytes,
+    // URL of the following page
+    next: Option<String>,
+}
+
+/// State of the HTTP client, kept for as long as the review is
+#[derive(Debug, Default)]
+struct HttpCache {
+    // pages by their URL, with the ETag to ask whether they changed
+    pages: HashMap<String, (String, Page)>,
+    // requests left and the time (seconds since the epoch) at which the limit resets
+    rate_limit: Option<(u64, u64)>,
+}
+
+const MAX_RETRIES: u32 = 3;
+// without a timeout, a stalled connection would never be retried
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+// longer waits for the rate limit are reported instead
+const MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(60);
+
+// exponential: 1s, 2s, 4s, ...
+fn backoff(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_secs(1 << attempt)
+}
+
+// `Retry-After` in seconds, the HTTP date form is not used by GitHub
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    let seconds = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+// the `Link` header names the neighbouring pages, e.g.,
+// `<https://api.github.com/...&page=2>; rel="next", <https://api.github.com/...&page=5>; rel="last"`
+fn next_page(link: &str) -> Option<String> {
+    link.split(',').find_map(|entry| {
+        let (url, params) = entry.split_once(';')?;
+        match params.split(';').any(|p| p.trim() == "rel=\"next\"") {
+            true => Some(
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_owned(),
+            ),
+            false => None,
+        }
+    })
+}
+
 fn save_to_disk<T: Serialize>(fname: &str, data: &T) -> Result<(), Error> {
     let f = std::fs::OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(fname)
         .expect("Couldn't open file");
     serde_yaml::to_writer(f, data).map_err(Error::from_yaml_error)
@@ -596,6 +1217,22 @@ fn save_to_disk<T: Serialize>(fname: &str, data: &T) -> Result<(), Error> {
 impl Review {
     const CONFIG_NAME: &'static str = ".review.yml";
 
+    fn default_per_page() -> u32 {
+        100
+    }
+
+    fn default_client() -> reqwest::Client {
+        // XXX: building fails only if TLS cannot be set up, fall back to a client without timeout
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    }
+
+    fn default_pending() -> String {
+        ".review_pending.yml".to_owned()
+    }
+
     pub fn from_args(args: &Args) -> Result<Self, Error> {
         let Some(interface) = args.platform else {
             return Err(Error::MissingConfig("interface".to_owned()));
@@ -637,68 +1274,300 @@ impl Review {
             auth: auth.to_owned(),
             comments: comments.to_owned(),
             local_repo,
+            matching: diff::Matching::default(),
+            per_page: match args.per_page {
+                Some(v) => v,
+                None => Review::default_per_page(),
+            },
+            pending: Review::default_pending(),
+            http: std::sync::Mutex::default(),
+            client: Review::default_client(),
         })
     }
 
     fn get_authentication(auth: &str) -> Result<String, Error> {
         fs::read_to_string(auth).map_err(Error::from_io_error)
     }
-    async fn get_comments_response(&self) -> Result<Response, Error> {
-        let request_url = match self.interface {
+    // first page of the comments, the following ones are linked from the response
+    fn comments_url(&self) -> String {
+        match self.interface {
             ReviewInterface::GitHub => {
                 format!(
-                    "https://api.{url}/repos/{owner}/{repo}/pulls/{prnum}/comments",
+                    "https://api.{url}/repos/{owner}/{repo}/pulls/{prnum}/comments?per_page={per_page}",
                     owner = &self.owner,
                     repo = &self.repo,
                     url = &self.url,
                     prnum = self.id,
+                    per_page = self.per_page,
                 )
             }
-        };
+        }
+    }
 
+    // requests the page, waiting for the rate limit and retrying transient failures, unchanged
+    // pages are taken from the cache
+    async fn get_page(&self, request_url: &str) -> Result<Page, Error> {
         let token = Review::get_authentication(&self.auth)?;
+        let mut attempt = 0;
 
-        let res = reqwest::Client::new()
-            .get(request_url)
-            .header("User-Agent", "clireview/0.0.1")
-            .bearer_auth(token)
-            .send()
-            .await
-            .map_err(Error::from_reqwest_error)?;
+        loop {
+            if let Some(wait) = self.rate_limit_wait() {
+                if wait > MAX_WAIT {
+                    return Err(Error::RateLimited(wait.as_secs()));
+                }
+                tokio::time::sleep(wait).await;
+            }
 
-        return match res.error_for_status_ref() {
-            Ok(_) => Ok(res),
-            Err(err) => match err.status() {
-                Some(v) => Err(Error::RequestError(v)),
-                None => Err(Error::SNH("something went wrong in weeds".to_owned())),
-            },
-        };
+            let cached = self.cached_page(request_url);
+            let mut request = self
+                .client
+                .get(request_url)
+                .header("User-Agent", "clireview/0.0.1")
+                .bearer_auth(&token);
+            if let Some((etag, _)) = &cached {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+
+            let res = match request.send().await {
+                Ok(res) => res,
+                Err(err) if attempt < MAX_RETRIES && (err.is_connect() || err.is_timeout()) => {
+                    tokio::time::sleep(backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(err) => return Err(Error::from_reqwest_error(err)),
+            };
+            self.remember_rate_limit(res.headers());
+
+            let status = res.status();
+            if let (reqwest::StatusCode::NOT_MODIFIED, Some((_, page))) = (status, &cached) {
+                return Ok(page.clone());
+            }
+            if status.is_success() {
+                let etag = res
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_owned());
+                let page = Page {
+                    next: res
+                        .headers()
+                        .get(reqwest::header::LINK)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(next_page),
+                    body: res.bytes().await.map_err(Error::from_reqwest_error)?,
+                };
+                if let (Some(etag), Ok(mut http)) = (etag, self.http.lock()) {
+                    http.pages
+                        .insert(request_url.to_owned(), (etag, page.clone()));
+                }
+                return Ok(page);
+            }
+
+            let wait = match status {
+                // (secondary) rate limits tell how long to wait
+                reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    retry_after(res.headers()).or_else(|| self.rate_limit_wait())
+                }
+                _ if status.is_server_error() => Some(backoff(attempt)),
+                _ => None,
+            };
+            match wait {
+                Some(wait) if wait > MAX_WAIT => return Err(Error::RateLimited(wait.as_secs())),
+                Some(wait) if attempt < MAX_RETRIES => {
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                _ => return Err(Error::RequestError(status)),
+            }
+        }
     }
 
-    async fn get_comments(&self) -> Result<Vec<ReviewComment>, Error> {
-        self.get_comments_response()
-            .await?
-            .json()
-            .await
-            .map_err(Error::from_reqwest_error)
+    fn cached_page(&self, request_url: &str) -> Option<(String, Page)> {
+        let http = self.http.lock().ok()?;
+        http.pages.get(request_url).cloned()
     }
 
-    async fn raw_comments(&self) -> Result<Bytes, Error> {
-        self.get_comments_response()
-            .await?
-            .bytes()
-            .await
-            .map_err(Error::from_reqwest_error)
+    fn remember_rate_limit(&self, headers: &reqwest::header::HeaderMap) {
+        let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        if let (Some(remaining), Some(reset)) =
+            (header("x-ratelimit-remaining"), header("x-ratelimit-reset"))
+        {
+            if let Ok(mut http) = self.http.lock() {
+                http.rate_limit = Some((remaining, reset));
+            }
+        }
     }
 
-    pub fn save_config(&self) -> Result<(), Error> {
-        save_to_disk(Self::CONFIG_NAME, self)
+    // time until the rate limit resets, if no requests are left
+    fn rate_limit_wait(&self) -> Option<std::time::Duration> {
+        let (remaining, reset) = self.http.lock().ok()?.rate_limit?;
+        if remaining > 0 {
+            return None;
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        Some(std::time::Duration::from_secs(reset.saturating_sub(now)))
+    }
+
+    fn files_url(&self) -> String {
+        match self.interface {
+            ReviewInterface::GitHub => {
+                format!(
+                    "https://api.{url}/repos/{owner}/{repo}/pulls/{prnum}/files?per_page={per_page}",
+                    owner = &self.owner,
+                    repo = &self.repo,
+                    url = &self.url,
+                    prnum = self.id,
+                    per_page = self.per_page,
+                )
+            }
+        }
+    }
+
+    // hunks of every file changed by the pull request, binary (or too large) files have none
+    async fn get_files(&self) -> Result<Vec<(String, Vec<Diff>)>, Error> {
+        let mut files = std::vec::Vec::new();
+
+        for file in self.get_list::<PullFile>(self.files_url()).await? {
+            let hunks = match file.patch {
+                Some(patch) => {
+                    Diff::from_hunks(&patch, &file.filename).map_err(Error::from_diff_error)?
+                }
+                None => std::vec::Vec::new(),
+            };
+            files.push((file.filename, hunks));
+        }
+
+        Ok(files)
+    }
+
+    async fn get_comments(&self) -> Result<Vec<ReviewComment>, Error> {
+        self.get_list(self.comments_url()).await
+    }
+
+    // every page as it was received
+    // the comments as they were received
+    async fn raw_comments(&self) -> Result<Vec<serde_json::Value>, Error> {
+        self.get_list(self.comments_url()).await
+    }
+
+    // bodies of the page at `url` and of all the pages following it
+    async fn get_pages(&self, url: String) -> Result<Vec<Bytes>, Error> {
+        let mut pages = std::vec::Vec::new();
+        let mut url = Some(url);
+
+        while let Some(request_url) = url {
+            let page = self.get_page(&request_url).await?;
+            pages.push(page.body);
+            url = page.next;
+        }
+
+        Ok(pages)
+    }
+
+    // every page is an array of its own, their elements are collected
+    async fn get_list<T: serde::de::DeserializeOwned>(&self, url: String) -> Result<Vec<T>, Error> {
+        let mut list = std::vec::Vec::new();
+
+        for page in self.get_pages(url).await? {
+            let mut received: Vec<T> =
+                serde_json::from_slice(&page).map_err(Error::from_json_error)?;
+            list.append(&mut received);
+        }
+
+        Ok(list)
+    }
+
+    pub fn save_config(&self) -> Result<(), Error> {
+        save_to_disk(Self::CONFIG_NAME, self)
     }
 
     pub fn save_comments(&self, comments: &Vec<ReviewComment>) -> Result<(), Error> {
         save_to_disk(&self.comments, comments)
     }
 
+    // applies a change that was made remotely to the saved comments, if there are any
+    fn update_saved_comments(
+        &self,
+        update: impl FnOnce(&mut Vec<ReviewComment>),
+    ) -> Result<(), Error> {
+        let f = match std::fs::File::open(&self.comments) {
+            Ok(f) => f,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(Error::from_io_error(err)),
+        };
+        let mut comments: Vec<ReviewComment> =
+            serde_yaml::from_reader(f).map_err(Error::from_yaml_error)?;
+        update(&mut comments);
+        self.save_comments(&comments)
+    }
+
+    // pending comments of another pull request (e.g., after `update --id`) are refused
+    fn load_pending(&self) -> Result<PendingReview, Error> {
+        let pending: PendingReview = match std::fs::File::open(&self.pending) {
+            Ok(f) => serde_yaml::from_reader(f).map_err(Error::from_yaml_error)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(PendingReview {
+                    owner: self.owner.to_owned(),
+                    repo: self.repo.to_owned(),
+                    id: self.id,
+                    comments: std::vec::Vec::new(),
+                })
+            }
+            Err(err) => return Err(Error::from_io_error(err)),
+        };
+
+        match pending.owner == self.owner && pending.repo == self.repo && pending.id == self.id {
+            true => Ok(pending),
+            false => Err(Error::InvalidComment(format!(
+                "pending comments are for {}/{}#{}, submit or remove {} first",
+                pending.owner, pending.repo, pending.id, self.pending
+            ))),
+        }
+    }
+
+    fn save_pending(&self, pending: &PendingReview) -> Result<(), Error> {
+        save_to_disk(&self.pending, pending)
+    }
+
+    fn comment_url(&self, id: u32) -> String {
+        match self.interface {
+            ReviewInterface::GitHub => {
+                format!(
+                    "https://api.{url}/repos/{owner}/{repo}/pulls/comments/{id}",
+                    owner = &self.owner,
+                    repo = &self.repo,
+                    url = &self.url,
+                    id = id,
+                )
+            }
+        }
+    }
+
+    // authenticates a request that changes the review, an unsuccessful status is an error
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Response, Error> {
+        let token = Review::get_authentication(&self.auth)?;
+
+        let res = request
+            .header("User-Agent", "clireview/0.0.1")
+            .header("Accept", "application/vnd.github+json")
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(Error::from_reqwest_error)?;
+
+        match res.error_for_status_ref() {
+            Ok(_) => Ok(res),
+            Err(err) => match err.status() {
+                Some(v) => Err(Error::RequestError(v)),
+                None => Err(Error::SNH("something went wrong in weeds".to_owned())),
+            },
+        }
+    }
+
     pub fn from_config(config: &str) -> Result<Self, Error> {
         let f = std::fs::File::open(config).map_err(Error::from_io_error)?; // XXX: move to input
                                                                             // parm (opening is not
@@ -746,6 +1615,11 @@ impl Review {
             None => self.local_repo.to_owned(),
         };
 
+        self.per_page = match args.per_page {
+            Some(v) => v,
+            None => self.per_page,
+        };
+
         Ok(())
     }
 }
@@ -764,6 +1638,9 @@ enum Command {
     Raw,
     Reply,
     Comment,
+    Edit,
+    Delete,
+    Submit,
 }
 
 // XXX: provide optional remote, otherwise see if .git directory is present and use default remote
@@ -805,6 +1682,23 @@ struct Args {
     commit_id: Option<String>,
     #[arg(short = 'x', long)]
     path: Option<String>,
+    #[arg(short = 'n', long)]
+    per_page: Option<u32>,
+    #[arg(long)]
+    line: Option<u32>,
+    #[arg(value_enum, long)]
+    side: Option<Side>,
+    #[arg(long)]
+    start_line: Option<u32>,
+    #[arg(value_enum, long)]
+    start_side: Option<Side>,
+    #[arg(value_enum, long)]
+    subject_type: Option<SubjectType>,
+    // keep the comment for the next review instead of posting it
+    #[arg(long)]
+    pending: bool,
+    #[arg(value_enum, long)]
+    verdict: Option<Verdict>,
 }
 
 // XXX: use `register_capability` to register new capabilities
@@ -820,9 +1714,33 @@ struct Args {
 struct Backend {
     client: Client,
     review: Review,
+    // commits that could not be recovered, to not fetch them again on every change
+    unreachable_commits: std::sync::Mutex<std::collections::HashSet<String>>,
+    // negotiated during initialization
+    position_encoding: std::sync::Mutex<PositionEncoding>,
+    // texts shown when hovering over commented lines, per document
+    hovers: std::sync::Mutex<HashMap<String, Vec<(lsp_types::Range, String)>>>,
 }
 
 impl Backend {
+    // missing commits were fetched on startup (see `serve_comments`), fetching blocks and must not
+    // happen while handling a request
+    // returns whether the commit is available, the error only the first time it is not
+    fn commit_available(&self, repo: &Repo, comm: &ReviewComment) -> Result<bool, Error> {
+        let id = comm.commented_commit();
+        if repo.has_commit(id) {
+            return Ok(true);
+        }
+
+        match self.unreachable_commits.lock() {
+            Ok(mut commits) => match commits.insert(id.to_owned()) {
+                true => Err(Error::UnreachableCommit(id.to_owned())),
+                false => Ok(false),
+            },
+            Err(_) => Ok(false),
+        }
+    }
+
     async fn on_change(&self, params: lsp_types::TextDocumentItem) {
         let comments = match self.review.get_comments().await {
             Ok(v) => v,
@@ -888,6 +1806,7 @@ impl Backend {
         // XXX: or directly serialize conversation in the first loop
         let mut lines_n_comments: Vec<(LineRange, &ReviewComment)> = Vec::new();
         let mut error_n_comments: Vec<&ReviewComment> = Vec::new();
+        let mut renames: HashMap<String, HashMap<String, String>> = HashMap::new();
 
         for &comm in &conversation.starter {
             self.client
@@ -896,15 +1815,77 @@ impl Backend {
                     format!("FUX| looking at: path: {}; uri: {}", comm.path, uri),
                 )
                 .await;
-            if uri.contains(&comm.path) {
-                #[cfg(feature = "debug")]
-                match comm.line_range(&params.text, &self.client).await {
-                    Ok(rng) => lines_n_comments.push((rng, comm)),
-                    Err(_) => error_n_comments.push(comm),
+            // XXX: git2::Repository is not Sync, so it must not be borrowed across `await`s
+            let available = match self.commit_available(&repo, comm) {
+                Ok(a) => a,
+                Err(e) => {
+                    self.client
+                        .log_message(
+                            lsp_types::MessageType::WARNING,
+                            format!("comment {}: {}", comm.id, e),
+                        )
+                        .await;
+                    false
+                }
+            };
+
+            // the file might have been renamed since the comment was made, renames are looked up
+            // once per commit
+            let commit = comm.commented_commit();
+            if available && !renames.contains_key(commit) {
+                let found = match repo.renames(commit) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.client
+                            .log_message(
+                                lsp_types::MessageType::WARNING,
+                                format!("commit {}: could not follow renames: {}", commit, e),
+                            )
+                            .await;
+                        HashMap::new()
+                    }
+                };
+                renames.insert(commit.to_owned(), found);
+            }
+            let path = match renames.get(commit) {
+                Some(r) => comm.current_path(r),
+                None => comm.path.to_owned(),
+            };
+
+            if uri.contains(&path) {
+                let tracked = match available {
+                    true => comm.track_lines(&repo, &params.text),
+                    false => Ok(None),
+                };
+                let tracked = match tracked {
+                    Ok(t) => t,
+                    Err(e) => {
+                        self.client
+                            .log_message(
+                                lsp_types::MessageType::WARNING,
+                                format!("could not track lines of {} with git: {}", comm.id, e),
+                            )
+                            .await;
+                        None
+                    }
                 };
-                #[cfg(not(feature = "debug"))]
-                match comm.line_range(&params.text) {
-                    Ok(rng) => lines_n_comments.push((rng, comm)),
+                let encoding = match self.position_encoding.lock() {
+                    Ok(encoding) => *encoding,
+                    Err(_) => PositionEncoding::Utf16,
+                };
+                match comm.line_range(&params.text, tracked, encoding, self.review.matching) {
+                    Ok(relocated) => {
+                        #[cfg(feature = "debug")]
+                        for event in &relocated.trace {
+                            self.client
+                                .log_message(
+                                    lsp_types::MessageType::ERROR,
+                                    format!("FUX| comment {}: {}", comm.id, event),
+                                )
+                                .await;
+                        }
+                        lines_n_comments.push((relocated.line_range, comm))
+                    }
                     Err(_) => error_n_comments.push(comm),
                 };
             }
@@ -926,18 +1907,55 @@ impl Backend {
             .iter()
             .map(|x| {
                 let comm = x.1;
-                let (msg, rng) = match x.0 {
-                    LineRange::Moved(v) => (format!("moved: "), v),
-                    LineRange::InPlace(v) => (format!(""), v),
-                    LineRange::Modified(v) => (format!("modified: "), v),
-                    LineRange::NotImplemented(v) => (format!("not implemented: "), v),
+                let (msg, loc, snippet) = match &x.0 {
+                    LineRange::Moved(v) => ("moved: ", v, None),
+                    LineRange::InPlace(v) => ("", v, None),
+                    LineRange::Modified(v) => ("modified: ", v, None),
+                    LineRange::NotImplemented(v) => ("not implemented: ", v, None),
+                    LineRange::File(v) => ("file: ", v, None),
+                    LineRange::Outdated(v, s) => ("outdated: ", v, Some(s)),
+                    LineRange::Deleted(v, s) => ("deleted: ", v, Some(s)),
                 };
-                lsp_types::Diagnostic::new_simple(
-                    rng,
-                    format!("{msg}{}", conversation.serialize(comm)),
-                )
+                let mut message = format!("{msg}{}", conversation.serialize(comm));
+                if let Some(snippet) = snippet {
+                    message.push_str("\ncommented code:\n");
+                    for line in snippet.lines() {
+                        message.push_str(&format!("> {line}\n"));
+                    }
+                }
+                message.push_str(&format!(
+                    "\n[{}, {:.0}% confidence]",
+                    loc.reason,
+                    loc.confidence * 100.0
+                ));
+                let mut diagnostic = lsp_types::Diagnostic::new_simple(loc.range, message);
+                // gone code is worth knowing about, not worth a warning
+                diagnostic.severity = match snippet {
+                    Some(_) => Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                    None => Some(loc.severity()),
+                };
+                diagnostic.code = Some(lsp_types::NumberOrString::String(
+                    loc.reason.code().to_owned(),
+                ));
+                diagnostic
+            })
+            .collect();
+
+        // hovers show the conversation together with the changed words of its hunk
+        let hovers: Vec<_> = lines_n_comments
+            .iter()
+            .map(|(line_range, comm)| {
+                let hunk = match Diff::from_only_hunk(&comm.diff_hunk, &comm.path) {
+                    Ok(diff) => diff.marked_text(),
+                    Err(_) => format!("{}\n", comm.diff_hunk),
+                };
+                let text = format!("{}\n\n```diff\n{hunk}```", conversation.serialize(comm));
+                (line_range.location().range, text)
             })
             .collect();
+        if let Ok(mut documents) = self.hovers.lock() {
+            documents.insert(params.uri.to_string(), hovers);
+        }
 
         self.client
             .publish_diagnostics(params.uri.clone(), diagnostics, Some(params.version))
@@ -945,23 +1963,51 @@ impl Backend {
     }
 }
 
+// commands offered to the client through `workspace/executeCommand`
+const EDIT_COMMAND: &str = "corey.editComment";
+const DELETE_COMMAND: &str = "corey.deleteComment";
+const QUEUE_COMMAND: &str = "corey.queueComment";
+const SUBMIT_COMMAND: &str = "corey.submitReview";
+
 #[tower_lsp::async_trait] // XXX is this needed? Y: otherwise Rust will complain about
                           // lifetime bounds of trait
 impl LanguageServer for Backend {
     async fn initialize(
         &self,
-        _: lsp_types::InitializeParams,
+        params: lsp_types::InitializeParams,
     ) -> jsonrpc::Result<lsp_types::InitializeResult> {
+        // the client lists the encodings it supports by preference, without a list it's UTF-16
+        let encoding = params
+            .capabilities
+            .general
+            .and_then(|general| general.position_encodings)
+            .and_then(|kinds| kinds.iter().find_map(PositionEncoding::from_kind))
+            .unwrap_or(PositionEncoding::Utf16);
+        if let Ok(mut negotiated) = self.position_encoding.lock() {
+            *negotiated = encoding;
+        }
+
         Ok(lsp_types::InitializeResult {
             server_info: None,
             // offset_encoding: None, // XXX: was in tower-lsp-boilerplate, why not here?
             capabilities: ServerCapabilities {
+                position_encoding: Some(encoding.kind()),
                 // The only thing we want to provide are `textDocument/diagnostic` respsonses.
                 // This does not need to register its own client and server capabilities.
                 // ...however, the server can register for the textDocument/diagnostic capability
                 text_document_sync: Some(lsp_types::TextDocumentSyncCapability::Kind(
                     lsp_types::TextDocumentSyncKind::FULL,
                 )),
+                hover_provider: Some(lsp_types::HoverProviderCapability::Simple(true)),
+                execute_command_provider: Some(lsp_types::ExecuteCommandOptions {
+                    commands: vec![
+                        EDIT_COMMAND.to_owned(),
+                        DELETE_COMMAND.to_owned(),
+                        QUEUE_COMMAND.to_owned(),
+                        SUBMIT_COMMAND.to_owned(),
+                    ],
+                    ..lsp_types::ExecuteCommandOptions::default()
+                }),
                 ..ServerCapabilities::default()
             },
         })
@@ -1010,6 +2056,105 @@ impl LanguageServer for Backend {
             .log_message(lsp_types::MessageType::INFO, "file closed!")
             .await;
     }
+
+    async fn hover(
+        &self,
+        params: lsp_types::HoverParams,
+    ) -> jsonrpc::Result<Option<lsp_types::Hover>> {
+        let position = params.text_document_position_params.position;
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_string();
+
+        // all conversations on the hovered line
+        let texts: Vec<String> = match self.hovers.lock() {
+            Ok(documents) => match documents.get(&uri) {
+                Some(hovers) => hovers
+                    .iter()
+                    .filter(|(range, _)| {
+                        range.start.line <= position.line && position.line <= range.end.line
+                    })
+                    .map(|(_, text)| text.to_owned())
+                    .collect(),
+                None => std::vec::Vec::new(),
+            },
+            Err(_) => std::vec::Vec::new(),
+        };
+
+        if texts.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(lsp_types::Hover {
+            contents: lsp_types::HoverContents::Markup(lsp_types::MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value: texts.join("\n\n---\n\n"),
+            }),
+            range: None,
+        }))
+    }
+
+    // editing and deleting take the comment ID (and the new body), queueing takes the path, the
+    // line and the body, submitting takes the verdict (e.g., `"APPROVE"`) and the summary
+    async fn execute_command(
+        &self,
+        params: lsp_types::ExecuteCommandParams,
+    ) -> jsonrpc::Result<Option<serde_json::Value>> {
+        let args = &params.arguments;
+        let number = |i: usize| {
+            args.get(i)
+                .and_then(|v| v.as_u64())
+                .and_then(|v| u32::try_from(v).ok())
+        };
+        let text = |i: usize| args.get(i).and_then(|v| v.as_str()).map(|v| v.to_owned());
+
+        let (result, done) = match params.command.as_str() {
+            EDIT_COMMAND => (
+                edit_comment(&self.review, number(0), text(1)).await,
+                "comment edited",
+            ),
+            DELETE_COMMAND => (
+                delete_comment(&self.review, number(0)).await,
+                "comment deleted",
+            ),
+            QUEUE_COMMAND => {
+                let placement = Placement {
+                    line: number(1),
+                    ..Placement::default()
+                };
+                (
+                    queue_comment(&self.review, text(2), text(0), placement).await,
+                    "comment added to the review",
+                )
+            }
+            SUBMIT_COMMAND => {
+                let verdict = args
+                    .first()
+                    .and_then(|v| serde_json::from_value(v.clone()).ok());
+                (
+                    submit_review(&self.review, verdict, text(1), None).await,
+                    "review submitted",
+                )
+            }
+            _ => return Err(jsonrpc::Error::method_not_found()),
+        };
+
+        match result {
+            Ok(()) => {
+                self.client
+                    .show_message(lsp_types::MessageType::INFO, done)
+                    .await;
+                Ok(None)
+            }
+            Err(e) => Err(jsonrpc::Error {
+                code: jsonrpc::ErrorCode::InternalError,
+                message: e.to_string().into(),
+                data: None,
+            }),
+        }
+    }
 }
 
 async fn serve_comments(review: Review) -> Result<(), Error> {
@@ -1017,9 +2162,27 @@ async fn serve_comments(review: Review) -> Result<(), Error> {
                                                  //      available data/comments)
     review.save_comments(&comments)?;
 
+    // the commits of comments might have been force-pushed away, fetch them before serving
+    // XXX: commits of comments made later on are not fetched
     let repo = Repo::new(&review.interface, &review.local_repo)?;
+    let commits: std::collections::HashSet<&str> = comments
+        .iter()
+        .filter(|c| c.in_reply_to_id.is_none())
+        .map(|c| c.commented_commit())
+        .collect();
+    for commit in commits {
+        if let Err(e) = repo.recover_commit(commit, &review) {
+            eprintln!("{}", e);
+        }
+    }
 
-    let (service, socket) = LspService::new(|client| Backend { client, review });
+    let (service, socket) = LspService::new(|client| Backend {
+        client,
+        review,
+        unreachable_commits: std::sync::Mutex::new(std::collections::HashSet::new()),
+        position_encoding: std::sync::Mutex::new(PositionEncoding::Utf16),
+        hovers: std::sync::Mutex::new(HashMap::new()),
+    });
 
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
@@ -1040,10 +2203,11 @@ async fn print_comments(review: Review) -> Result<(), Error> {
 }
 
 async fn print_raw(review: Review) -> Result<(), Error> {
+    // the comments of all pages as a single array
     let comments = review.raw_comments().await?;
     print!(
         "{}",
-        std::str::from_utf8(&comments).map_err(Error::from_utf8_error)?
+        serde_json::to_string(&comments).map_err(Error::from_json_error)?
     );
 
     Ok(())
@@ -1068,12 +2232,9 @@ async fn reply_to_comment(
         None => return Err(Error::MissingConfig("ID".to_owned())),
     };
 
-    let token = Review::get_authentication(&review.auth)?;
-
     let request_body = Reply { body };
 
-    let client = reqwest::Client::new();
-    let res = client
+    let request = review.client
         .post(
             format!("https://api.{URL}/repos/{OWNER}/{REPO}/pulls/{PULL_NUMBER}/comments/{COMMENT_ID}/replies",
                 URL = &review.url,
@@ -1082,21 +2243,168 @@ async fn reply_to_comment(
                 PULL_NUMBER = review.id,
                 COMMENT_ID = id),
         )
-        .json(&request_body)
-        .header("User-Agent", "clireview/0.0.1")
-        .header("Accept", "application/vnd.github+json")
-        .bearer_auth(token)
-        .send()
-        .await
-        .map_err(Error::from_reqwest_error)?;
+        .json(&request_body);
+    review.send(request).await?;
 
-    return match res.error_for_status_ref() {
-        Ok(_) => Ok(()),
-        Err(err) => match err.status() {
-            Some(v) => Err(Error::RequestError(v)),
-            None => Err(Error::SNH("something went wrong in weeds".to_owned())),
-        },
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Edit {
+    body: String,
+}
+
+async fn edit_comment(review: &Review, id: Option<u32>, body: Option<String>) -> Result<(), Error> {
+    let body = match body {
+        Some(b) => b,
+        None => return Err(Error::MissingConfig("comment body".to_owned())),
     };
+    let id = match id {
+        Some(i) => i,
+        None => return Err(Error::MissingConfig("ID".to_owned())),
+    };
+
+    let request = review
+        .client
+        .patch(review.comment_url(id))
+        .json(&Edit { body });
+    let res = review.send(request).await?;
+
+    // the response is the edited comment
+    let edited: ReviewComment = res.json().await.map_err(Error::from_reqwest_error)?;
+    review.update_saved_comments(|comments| {
+        if let Some(comment) = comments.iter_mut().find(|c| c.id == id) {
+            *comment = edited;
+        }
+    })
+}
+
+async fn delete_comment(review: &Review, id: Option<u32>) -> Result<(), Error> {
+    let id = match id {
+        Some(i) => i,
+        None => return Err(Error::MissingConfig("ID".to_owned())),
+    };
+
+    let request = review.client.delete(review.comment_url(id));
+    review.send(request).await?;
+
+    // XXX: replies stay, GitHub shows them without the comment they replied to
+    review.update_saved_comments(|comments| comments.retain(|c| c.id != id))
+}
+
+/// Where a new comment goes, as accepted by `pulls/N/comments`
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Placement {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    side: Option<Side>,
+    // first line of a ranged comment, `line` is the last one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_side: Option<Side>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject_type: Option<SubjectType>,
+}
+
+impl Placement {
+    // fills in the defaults and checks that the lines are part of a single hunk of `path`, which
+    // is what GitHub accepts
+    fn validate(self, path: &str, files: &[(String, Vec<Diff>)]) -> Result<Placement, Error> {
+        let Some((_, hunks)) = files.iter().find(|(name, _)| name == path) else {
+            return Err(Error::InvalidComment(format!(
+                "{} is not changed by the pull request",
+                path
+            )));
+        };
+
+        let subject_type = match (self.subject_type, self.line) {
+            (Some(t), _) => t,
+            (None, Some(_)) => SubjectType::Line,
+            (None, None) => SubjectType::File,
+        };
+
+        match subject_type {
+            SubjectType::File => {
+                if self.line.is_some() || self.start_line.is_some() {
+                    return Err(Error::InvalidComment(
+                        "file-level comments have no lines".to_owned(),
+                    ));
+                }
+                Ok(Placement {
+                    subject_type: Some(SubjectType::File),
+                    ..Placement::default()
+                })
+            }
+            SubjectType::Line => {
+                let Some(line) = self.line else {
+                    return Err(Error::MissingConfig("line".to_owned()));
+                };
+                let side = self.side.unwrap_or(Side::Right);
+                let start_side = match (self.start_line, self.start_side) {
+                    (Some(_), start_side) => Some(start_side.unwrap_or(side)),
+                    (None, Some(_)) => {
+                        return Err(Error::InvalidComment(
+                            "start side without start line".to_owned(),
+                        ))
+                    }
+                    (None, None) => None,
+                };
+
+                let Some(hunk) = hunks.iter().find(|h| covers(h, line, side)) else {
+                    return Err(Error::InvalidComment(format!(
+                        "line {} is not part of the diff of {}",
+                        line, path
+                    )));
+                };
+                if let (Some(start_line), Some(start_side)) = (self.start_line, start_side) {
+                    if !covers(hunk, start_line, start_side) {
+                        return Err(Error::InvalidComment(format!(
+                            "lines {} to {} are not part of the same hunk",
+                            start_line, line
+                        )));
+                    }
+                    // lines on different sides are compared by their number on the left
+                    let precedes = match (start_side, side) {
+                        (Side::Left, Side::Right) => match hunk.to_original_line(line) {
+                            Some(diff::LineMapping::Kept(l))
+                            | Some(diff::LineMapping::Added(l)) => start_line < l,
+                            _ => false,
+                        },
+                        // added lines come before the left line they were inserted before
+                        (Side::Right, Side::Left) => match hunk.to_original_line(start_line) {
+                            Some(diff::LineMapping::Kept(l)) => l < line,
+                            Some(diff::LineMapping::Added(l)) => l <= line,
+                            _ => false,
+                        },
+                        _ => start_line < line,
+                    };
+                    if !precedes {
+                        return Err(Error::InvalidComment(
+                            "start line must precede line".to_owned(),
+                        ));
+                    }
+                }
+
+                Ok(Placement {
+                    line: Some(line),
+                    side: Some(side),
+                    start_line: self.start_line,
+                    start_side,
+                    subject_type: Some(SubjectType::Line),
+                })
+            }
+        }
+    }
+}
+
+// whether the hunk shows the line on the given side
+fn covers(hunk: &Diff, line: u32, side: Side) -> bool {
+    match side {
+        Side::Left => hunk.original_line_range().contains(&line),
+        Side::Right => hunk.line_range().contains(&line),
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1104,6 +2412,8 @@ struct Comment {
     body: String,
     commit_id: String,
     path: String,
+    #[serde(flatten)]
+    placement: Placement,
 }
 
 async fn create_comment(
@@ -1111,6 +2421,7 @@ async fn create_comment(
     commit_id: Option<String>,
     body: Option<String>,
     path: Option<String>,
+    placement: Placement,
 ) -> Result<(), Error> {
     let body = match body {
         Some(b) => b,
@@ -1125,15 +2436,17 @@ async fn create_comment(
         None => return Err(Error::MissingConfig("relative file path".to_owned())),
     };
 
+    let files = review.get_files().await?;
+    let placement = placement.validate(&path, &files)?;
+
     let request_body = Comment {
         body,
         commit_id,
         path,
+        placement,
     };
-    let token = Review::get_authentication(&review.auth)?;
-    let client = reqwest::Client::new();
-
-    let res = client
+    let request = review
+        .client
         .post(format!(
             "https://api.{URL}/repos/{OWNER}/{REPO}/pulls/{PULL_NUMBER}/comments",
             URL = &review.url,
@@ -1141,21 +2454,149 @@ async fn create_comment(
             REPO = &review.repo,
             PULL_NUMBER = review.id,
         ))
-        .json(&request_body)
-        .header("User-Agent", "clireview/0.0.1")
-        .header("Accept", "application/vnd.github+json")
-        .bearer_auth(token)
-        .send()
-        .await
-        .map_err(Error::from_reqwest_error)?;
+        .json(&request_body);
+    review.send(request).await?;
+
+    Ok(())
+}
+
+/// Comments waiting to be submitted as part of a review of a pull request
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingReview {
+    owner: String,
+    repo: String,
+    id: u32,
+    comments: Vec<PendingComment>,
+}
+
+/// A comment waiting to be submitted as part of a review
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingComment {
+    path: String,
+    body: String,
+    #[serde(flatten)]
+    placement: Placement,
+}
 
-    return match res.error_for_status_ref() {
-        Ok(_) => Ok(()),
-        Err(err) => match err.status() {
-            Some(v) => Err(Error::RequestError(v)),
-            None => Err(Error::SNH("something went wrong in weeds".to_owned())),
+/// Outcome of a review
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Verdict {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Submission {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    event: Verdict,
+    comments: Vec<PendingComment>,
+}
+
+// validated right away, the diff might not be at hand anymore when the review is submitted
+async fn queue_comment(
+    review: &Review,
+    body: Option<String>,
+    path: Option<String>,
+    placement: Placement,
+) -> Result<(), Error> {
+    let body = match body {
+        Some(b) => b,
+        None => return Err(Error::MissingConfig("comment body".to_owned())),
+    };
+    let path = match path {
+        Some(p) => p,
+        None => return Err(Error::MissingConfig("relative file path".to_owned())),
+    };
+
+    let files = review.get_files().await?;
+    let placement = placement.validate(&path, &files)?;
+    // the comments of a review are always placed on lines
+    if placement.subject_type == Some(SubjectType::File) {
+        return Err(Error::InvalidComment(
+            "file-level comments cannot be part of a review".to_owned(),
+        ));
+    }
+
+    let mut pending = review.load_pending()?;
+    pending.comments.push(PendingComment {
+        path,
+        body,
+        placement: Placement {
+            subject_type: None,
+            ..placement
         },
+    });
+    review.save_pending(&pending)
+}
+
+// posts all pending comments at once, they are kept if that fails
+async fn submit_review(
+    review: &Review,
+    verdict: Option<Verdict>,
+    body: Option<String>,
+    commit_id: Option<String>,
+) -> Result<(), Error> {
+    let verdict = match verdict {
+        Some(v) => v,
+        None => return Err(Error::MissingConfig("verdict".to_owned())),
     };
+    // only an approval may go without a summary
+    if body.is_none() && verdict != Verdict::Approve {
+        return Err(Error::MissingConfig("review body".to_owned()));
+    }
+
+    // the pull request might have changed since the comments were queued
+    let pending = review.load_pending()?;
+    if !pending.comments.is_empty() {
+        let files = review.get_files().await?;
+        for comment in &pending.comments {
+            comment.placement.clone().validate(&comment.path, &files)?;
+        }
+    }
+
+    let request_body = Submission {
+        commit_id,
+        body,
+        event: verdict,
+        comments: pending.comments,
+    };
+    let request = review
+        .client
+        .post(format!(
+            "https://api.{URL}/repos/{OWNER}/{REPO}/pulls/{PULL_NUMBER}/reviews",
+            URL = &review.url,
+            OWNER = &review.owner,
+            REPO = &review.repo,
+            PULL_NUMBER = review.id,
+        ))
+        .json(&request_body);
+    review.send(request).await?;
+
+    match std::fs::remove_file(&review.pending) {
+        Ok(()) => (),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => return Err(Error::from_io_error(err)),
+    }
+
+    // the submitted comments are part of the review now, the review stands even if they cannot be
+    // saved
+    let refreshed = review
+        .get_comments()
+        .await
+        .and_then(|comments| review.save_comments(&comments));
+    if let Err(e) = refreshed {
+        eprintln!(
+            "review submitted, but the saved comments could not be refreshed: {}",
+            e
+        );
+    }
+
+    Ok(())
 }
 
 // XXX: decide on semantics
@@ -1195,7 +2636,10 @@ async fn main() -> Result<(), Error> {
         | Command::Print
         | Command::Raw
         | Command::Reply
-        | Command::Comment => Review::from_config(Review::CONFIG_NAME)?,
+        | Command::Comment
+        | Command::Edit
+        | Command::Delete
+        | Command::Submit => Review::from_config(Review::CONFIG_NAME)?,
     };
 
     pr.update_config(&args)?;
@@ -1206,8 +2650,410 @@ async fn main() -> Result<(), Error> {
         Command::Run => serve_comments(pr).await?,
         Command::Print => print_comments(pr).await?,
         Command::Raw => print_raw(pr).await?,
-        Command::Comment => create_comment(pr, args.commit_id, args.body, args.path).await?,
+        Command::Comment => {
+            let placement = Placement {
+                line: args.line,
+                side: args.side,
+                start_line: args.start_line,
+                start_side: args.start_side,
+                subject_type: args.subject_type,
+            };
+            match args.pending {
+                true => queue_comment(&pr, args.body, args.path, placement).await?,
+                false => {
+                    create_comment(pr, args.commit_id, args.body, args.path, placement).await?
+                }
+            }
+        }
         Command::Reply => reply_to_comment(pr, args.comment, args.body).await?,
+        Command::Edit => edit_comment(&pr, args.comment, args.body).await?,
+        Command::Delete => delete_comment(&pr, args.comment).await?,
+        Command::Submit => submit_review(&pr, args.verdict, args.body, args.commit_id).await?,
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUNK: &str = "\
+@@ -1,7 +1,7 @@ fn main() {
+ let a = 1;
+ let b = 2;
+ let c = 3;
+-let d = 4;
++let d = 40;
+ let e = 5;
+ let f = 6;
+ let g = 7;";
+
+    const TEXT: &str = "\
+let a = 1;
+let b = 2;
+let c = 3;
+let d = 40;
+let e = 5;
+let f = 6;
+let g = 7;
+";
+
+    fn comment(diff_hunk: &str, side: &str, line: u32) -> ReviewComment {
+        ReviewComment {
+            id: 1,
+            in_reply_to_id: None,
+            body: "body".to_owned(),
+            commit_id: "c0ffee".to_owned(),
+            original_commit_id: "c0ffee".to_owned(),
+            line: Some(line),
+            original_line: Some(line),
+            start_line: None,
+            original_start_line: None,
+            user: User {
+                login: "reviewer".to_owned(),
+            },
+            diff_hunk: diff_hunk.to_owned(),
+            path: "src/main.rs".to_owned(),
+            subject_type: Some("line".to_owned()),
+            start_side: None,
+            side: Some(side.to_owned()),
+        }
+    }
+
+    // kind of the result, its 0-based first and last line and the reason
+    fn locate(
+        comment: &ReviewComment,
+        text: &str,
+        tracked: Option<diff::Tracking>,
+        matching: diff::Matching,
+    ) -> (&'static str, u32, u32, Reason) {
+        let relocated = comment
+            .line_range(text, tracked, PositionEncoding::Utf16, matching)
+            .unwrap();
+        let kind = match &relocated.line_range {
+            LineRange::InPlace(_) => "in place",
+            LineRange::Moved(_) => "moved",
+            LineRange::Modified(_) => "modified",
+            LineRange::NotImplemented(_) => "not implemented",
+            LineRange::File(_) => "file",
+            LineRange::Outdated(..) => "outdated",
+            LineRange::Deleted(..) => "deleted",
+        };
+        let location = relocated.line_range.location();
+        (
+            kind,
+            location.range.start.line,
+            location.range.end.line,
+            location.reason,
+        )
+    }
+
+    #[test]
+    fn in_place() {
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            TEXT,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("in place", 3, 3, Reason::ExactMatch));
+    }
+
+    #[test]
+    fn moved() {
+        let text = format!("// one\n// two\n{}", TEXT);
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("moved", 5, 5, Reason::OffsetMatch));
+    }
+
+    #[test]
+    fn fuzzy() {
+        let text = TEXT.replace("let a = 1;", "let a = 100;");
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("in place", 3, 3, Reason::FuzzyMatch));
+    }
+
+    #[test]
+    fn whitespace() {
+        let text = TEXT.replace("let d = 40;", "    let  d = 40;");
+        let exact = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(exact.3, Reason::ContextAnchored);
+
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            None,
+            diff::Matching::IgnoreWhitespace,
+        );
+        assert_eq!(found, ("in place", 3, 3, Reason::WhitespaceMatch));
+    }
+
+    #[test]
+    fn context_anchored() {
+        let text = format!("// one\n{}", TEXT.replace("let d = 40;", "let d = 41;"));
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("modified", 4, 4, Reason::ContextAnchored));
+    }
+
+    #[test]
+    fn outdated() {
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            "fn something_else() {}\n",
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("outdated", 0, 0, Reason::NotFound));
+    }
+
+    #[test]
+    fn tracked_as_deleted() {
+        let text = TEXT.replace("let d = 40;\n", "");
+        let tracked = Some(diff::Tracking::Deleted(4));
+        let found = locate(
+            &comment(HUNK, "RIGHT", 4),
+            &text,
+            tracked,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("deleted", 3, 3, Reason::GitTracked));
+    }
+
+    #[test]
+    fn left_deleted() {
+        let found = locate(&comment(HUNK, "LEFT", 4), TEXT, None, diff::Matching::Exact);
+        assert_eq!(found, ("deleted", 3, 3, Reason::ContextAnchored));
+    }
+
+    #[test]
+    fn file_level() {
+        let mut comm = comment(HUNK, "RIGHT", 4);
+        comm.subject_type = Some("file".to_owned());
+        comm.line = None;
+        comm.original_line = None;
+        let found = locate(&comm, TEXT, None, diff::Matching::Exact);
+        assert_eq!(found, ("file", 0, 0, Reason::WholeFile));
+    }
+
+    #[test]
+    fn first_line_gone() {
+        // the trailing context would place the comment before the first line
+        let hunk = "@@ -1,3 +1,3 @@\n-old\n+new\n ctx1\n ctx2";
+        let found = locate(
+            &comment(hunk, "RIGHT", 1),
+            "ctx1\nctx2\n",
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("outdated", 0, 0, Reason::NotFound));
+    }
+
+    #[test]
+    fn char_range_line_zero() {
+        let range = char_range("a\n", 0..1, PositionEncoding::Utf16);
+        assert_eq!(range.start.line, 0);
+        assert_eq!(range.end.line, 0);
+    }
+
+    fn placement(start_line: u32, start_side: Side, line: u32, side: Side) -> Placement {
+        Placement {
+            line: Some(line),
+            side: Some(side),
+            start_line: Some(start_line),
+            start_side: Some(start_side),
+            subject_type: None,
+        }
+    }
+
+    #[test]
+    fn ranges_across_sides() {
+        let hunks = Diff::from_hunks(HUNK, "src/main.rs").unwrap();
+        let files = vec![("src/main.rs".to_owned(), hunks)];
+
+        // from the removed line to the one replacing it
+        let removed_to_added = placement(4, Side::Left, 4, Side::Right);
+        assert!(removed_to_added.validate("src/main.rs", &files).is_ok());
+
+        // from the added line to the context after it
+        let added_to_context = placement(4, Side::Right, 5, Side::Left);
+        assert!(added_to_context.validate("src/main.rs", &files).is_ok());
+
+        // the removed line comes before the replacing one
+        let backwards = placement(4, Side::Right, 4, Side::Left);
+        assert!(backwards.validate("src/main.rs", &files).is_err());
+
+        let before_start = placement(5, Side::Left, 4, Side::Right);
+        assert!(before_start.validate("src/main.rs", &files).is_err());
+    }
+
+    // as read from a configuration file
+    fn review() -> Review {
+        let config = "interface: GitHub\nowner: o\nrepo: r\nurl: github.com\nid: 1\n\
+                      auth: token\ncomments: c.yml\nlocal_repo: ./\n";
+        serde_yaml::from_str(config).unwrap()
+    }
+
+    #[test]
+    fn pending_of_other_pull_request() {
+        let pending = std::env::temp_dir().join("corey_pending_of_other_pull_request.yml");
+        let mut review = review();
+        review.pending = pending.display().to_string();
+        let mut queued = review.load_pending().unwrap();
+        queued.comments.push(PendingComment {
+            path: "src/main.rs".to_owned(),
+            body: "body".to_owned(),
+            placement: Placement::default(),
+        });
+        review.save_pending(&queued).unwrap();
+        assert_eq!(review.load_pending().unwrap().comments.len(), 1);
+
+        review.id = 2;
+        assert!(review.load_pending().is_err());
+        std::fs::remove_file(pending).unwrap();
+    }
+
+    #[test]
+    fn anchored_to_nearest_context() {
+        let hunk = "@@ -28,4 +28,4 @@\n     }\n }\n-old\n+new\n fn other() {";
+        let mut lines: Vec<String> = (1..=27).map(|i| format!("let v{i} = {i};")).collect();
+        // the same closing brackets much earlier in the file
+        lines[4] = "    }".to_owned();
+        lines[5] = "}".to_owned();
+        lines.extend(["    }", "}", "changed", "fn other() {"].map(|l| l.to_owned()));
+        let text = lines.join("\n") + "\n";
+
+        let found = locate(
+            &comment(hunk, "RIGHT", 30),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("modified", 29, 29, Reason::ContextAnchored));
+    }
+
+    #[test]
+    fn anchored_to_whole_lines() {
+        let hunk = "@@ -10,2 +10,2 @@\n b = 1;\n-old\n+new";
+        let mut lines: Vec<String> = (1..=11).map(|i| format!("let v{i} = {i};")).collect();
+        // contains the context, but as part of a longer line
+        lines[1] = "ab = 1;".to_owned();
+        lines[9] = "b = 1;".to_owned();
+        lines[10] = "changed".to_owned();
+        let text = lines.join("\n") + "\n";
+
+        let found = locate(
+            &comment(hunk, "RIGHT", 11),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("modified", 10, 10, Reason::ContextAnchored));
+    }
+
+    #[test]
+    fn removed_lines_anchored_to_nearest_context() {
+        let hunk = "@@ -20,3 +20,2 @@\n a = 1;\n-old\n b = 2;";
+        let mut lines: Vec<String> = (1..=21).map(|i| format!("let v{i} = {i};")).collect();
+        lines[2] = "a = 1;".to_owned();
+        lines[19] = "a = 1;".to_owned();
+        lines[20] = "b = 2;".to_owned();
+        let text = lines.join("\n") + "\n";
+
+        let found = locate(
+            &comment(hunk, "LEFT", 21),
+            &text,
+            None,
+            diff::Matching::Exact,
+        );
+        assert_eq!(found, ("deleted", 20, 20, Reason::ContextAnchored));
+    }
+
+    #[test]
+    fn next_page_from_link() {
+        let link = "<https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=2>; \
+                    rel=\"next\", \
+                    <https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=5>; \
+                    rel=\"last\"";
+        assert_eq!(
+            next_page(link).as_deref(),
+            Some("https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=2")
+        );
+
+        // the last page only links back
+        let link =
+            "<https://api.github.com/repositories/1/pulls/2/comments?page=4>; rel=\"prev\", \
+                    <https://api.github.com/repositories/1/pulls/2/comments?page=1>; rel=\"first\"";
+        assert_eq!(next_page(link), None);
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(reqwest::header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(
+            retry_after(&headers),
+            Some(std::time::Duration::from_secs(120))
+        );
+
+        // dates are not understood
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+        headers.insert(reqwest::header::RETRY_AFTER, date.parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let waits: Vec<u64> = (0..MAX_RETRIES).map(|a| backoff(a).as_secs()).collect();
+        assert_eq!(waits, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let review = review();
+        assert_eq!(review.rate_limit_wait(), None);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-reset", (now + 30).into());
+
+        // requests are left
+        headers.insert("x-ratelimit-remaining", 10.into());
+        review.remember_rate_limit(&headers);
+        assert_eq!(review.rate_limit_wait(), None);
+
+        headers.insert("x-ratelimit-remaining", 0.into());
+        review.remember_rate_limit(&headers);
+        let wait = review.rate_limit_wait().unwrap().as_secs();
+        assert!((29..=30).contains(&wait));
+
+        // the limit has already been reset
+        headers.insert("x-ratelimit-reset", (now - 5).into());
+        review.remember_rate_limit(&headers);
+        assert_eq!(review.rate_limit_wait(), Some(std::time::Duration::ZERO));
+    }
+}